@@ -0,0 +1,160 @@
+use std::io;
+use std::net::{TcpStream, ToSocketAddrs};
+
+use crate::game::{read_available, write_pending, ClientData};
+use crate::protocol::{
+	ClientPacket, ControlPacket, Packet, PacketBuf, PacketProtocol, ServerPacket, StateMessage,
+	WelcomePacket,
+};
+
+// Reference client: sends inputs and collects the world state the server broadcasts.
+pub struct GameClient {
+	stream: TcpStream,
+	buf: PacketBuf,
+	// Bytes queued for the server that the socket did not accept yet.
+	outbound: Vec<u8>,
+	welcome: Option<WelcomePacket>,
+	last_processed_seq: u32,
+}
+
+impl GameClient {
+	pub fn connect(address: impl ToSocketAddrs) -> io::Result<Self> {
+		let stream = TcpStream::connect(address)?;
+		stream.set_nonblocking(true)?;
+
+		Ok(GameClient {
+			stream,
+			buf: PacketBuf::new(),
+			outbound: Vec::new(),
+			welcome: None,
+			last_processed_seq: 0,
+		})
+	}
+
+	// Set once the server's welcome arrived through `poll_snapshots`.
+	pub fn welcome(&self) -> Option<&WelcomePacket> {
+		self.welcome.as_ref()
+	}
+
+	pub fn last_processed_seq(&self) -> u32 {
+		self.last_processed_seq
+	}
+
+	pub fn send_input(&mut self, input: ClientData) -> anyhow::Result<()> {
+		let packet = PacketProtocol::Zero(ClientPacket::from(&input)).serialize()?;
+		self.outbound.extend(packet);
+		write_pending(&mut self.stream, &mut self.outbound)?;
+		Ok(())
+	}
+
+	// Asks the server to spawn or remove this client's ship.
+	pub fn send_control(&mut self, control: ControlPacket) -> anyhow::Result<()> {
+		let packet = PacketProtocol::Zero(control).serialize()?;
+		self.outbound.extend(packet);
+		write_pending(&mut self.stream, &mut self.outbound)?;
+		Ok(())
+	}
+
+	// Reads everything the server sent so far, returning the ships of every state received.
+	pub fn poll_snapshots(&mut self) -> anyhow::Result<Vec<ServerPacket>> {
+		write_pending(&mut self.stream, &mut self.outbound)?;
+
+		let mut ships = Vec::new();
+		let mut bytes = [0; 4096];
+		loop {
+			let size_read = read_available(&mut self.stream, &mut bytes)?;
+			let mut packet = self.buf.process::<ServerPacket>(&bytes[..size_read])?;
+			while let Some(PacketProtocol::Raw {
+				id,
+				protocol,
+				content,
+			}) = packet
+			{
+				if id == WelcomePacket::id() {
+					let welcome = PacketProtocol::<WelcomePacket>::Raw {
+						id,
+						protocol,
+						content,
+					};
+					self.welcome = Some(welcome.deserialize()?);
+				} else if id == ServerPacket::id() {
+					let state = StateMessage::decode(&content)?;
+					self.last_processed_seq = state.last_processed_seq;
+					ships.extend(state.ships);
+				}
+
+				packet = self.buf.process(&[])?;
+			}
+
+			if size_read == 0 {
+				break;
+			}
+		}
+
+		Ok(ships)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::net::TcpListener;
+	use std::thread;
+	use std::time::Duration;
+
+	use super::*;
+	use crate::game::{Game, ShipId};
+
+	// Runs server ticks until the client saw a state that satisfies `done`.
+	fn poll_until(
+		game: &mut Game,
+		client: &mut GameClient,
+		mut done: impl FnMut(&GameClient, &[ServerPacket]) -> bool,
+	) {
+		for _ in 0..200 {
+			game.net_poll();
+			game.apply_all_inputs();
+			game.step(0.125);
+			game.broadcast_state();
+
+			let ships = client.poll_snapshots().unwrap();
+			if done(client, &ships) {
+				return;
+			}
+			thread::sleep(Duration::from_millis(5));
+		}
+		panic!("the client never got the expected state");
+	}
+
+	#[test]
+	fn client_sees_its_ship_move() {
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let mut client = GameClient::connect(listener.local_addr().unwrap()).unwrap();
+		let (stream, _) = listener.accept().unwrap();
+		stream.set_nonblocking(true).unwrap();
+
+		let mut game = Game::new();
+		let id = game.new_player(stream).unwrap().unwrap();
+
+		poll_until(&mut game, &mut client, |client, _| {
+			client.welcome().is_some()
+		});
+		let your_ship_id = client.welcome().unwrap().your_ship_id;
+		assert_eq!(ShipId(your_ship_id as i32), id);
+
+		client
+			.send_input(ClientData {
+				ship_id: id,
+				orientation: 0.0,
+				propulsor: [true, false, false, false],
+				client_tick: 1,
+				sequence: 1,
+			})
+			.unwrap();
+		poll_until(&mut game, &mut client, |client, ships| {
+			client.last_processed_seq() == 1
+				&& ships
+					.iter()
+					.any(|ship| ship.player_id == your_ship_id && ship.position.length() > 0.0)
+		});
+	}
+}
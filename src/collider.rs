@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+
+use crate::vector::Vector;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Circle {
+	pub center: Vector,
+	pub radius: f32,
+}
+
+// Axis aligned box from `min` to `max`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Aabb {
+	pub min: Vector,
+	pub max: Vector,
+}
+
+impl Aabb {
+	pub fn closest_point(&self, point: Vector) -> Vector {
+		Vector {
+			x: point.x.clamp(self.min.x, self.max.x),
+			y: point.y.clamp(self.min.y, self.max.y),
+		}
+	}
+}
+
+// Every supported hitbox. Intersection is resolved per pair of shapes, so adding a shape
+// means adding its pairs to `Shape::intersects`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Shape {
+	Circle(Circle),
+	Aabb(Aabb),
+}
+
+impl Shape {
+	pub fn intersects(&self, other: &Shape) -> bool {
+		match (self, other) {
+			(Shape::Circle(a), Shape::Circle(b)) => {
+				(a.center - b.center).length() <= a.radius + b.radius
+			}
+			(Shape::Circle(circle), Shape::Aabb(aabb))
+			| (Shape::Aabb(aabb), Shape::Circle(circle)) => {
+				(circle.center - aabb.closest_point(circle.center)).length() <= circle.radius
+			}
+			(Shape::Aabb(a), Shape::Aabb(b)) => {
+				a.min.x <= b.max.x && b.min.x <= a.max.x && a.min.y <= b.max.y && b.min.y <= a.max.y
+			}
+		}
+	}
+
+	// Shortest translation moving the circle out of the shape, none when they do not overlap.
+	pub fn separation(&self, circle: &Circle) -> Option<Vector> {
+		match self {
+			Shape::Circle(obstacle) => {
+				let delta = circle.center - obstacle.center;
+				let distance = delta.length();
+				let reach = circle.radius + obstacle.radius;
+				if distance >= reach {
+					return None;
+				}
+				// Concentric circles have no direction of their own, any will do.
+				if distance == 0.0 {
+					return Some(Vector { x: reach, y: 0.0 });
+				}
+
+				Some(delta / distance * (reach - distance))
+			}
+			Shape::Aabb(aabb) => {
+				let delta = circle.center - aabb.closest_point(circle.center);
+				let distance = delta.length();
+				if distance >= circle.radius {
+					return None;
+				}
+				if distance > 0.0 {
+					return Some(delta / distance * (circle.radius - distance));
+				}
+
+				// The center is inside the box, leave through the nearest side.
+				let sides = [
+					(circle.center.x - aabb.min.x, Vector { x: -1.0, y: 0.0 }),
+					(aabb.max.x - circle.center.x, Vector { x: 1.0, y: 0.0 }),
+					(circle.center.y - aabb.min.y, Vector { x: 0.0, y: -1.0 }),
+					(aabb.max.y - circle.center.y, Vector { x: 0.0, y: 1.0 }),
+				];
+				let (depth, normal) = sides
+					.into_iter()
+					.min_by(|a, b| a.0.total_cmp(&b.0))
+					.unwrap_or((0.0, Vector { x: 0.0, y: 0.0 }));
+
+				Some(normal * (depth + circle.radius))
+			}
+		}
+	}
+}
+
+pub trait Collider {
+	fn shape(&self) -> Shape;
+
+	fn intersects(&self, other: &dyn Collider) -> bool {
+		self.shape().intersects(&other.shape())
+	}
+}
+
+impl Collider for Circle {
+	fn shape(&self) -> Shape {
+		Shape::Circle(*self)
+	}
+}
+
+impl Collider for Aabb {
+	fn shape(&self) -> Shape {
+		Shape::Aabb(*self)
+	}
+}
+
+impl Collider for Shape {
+	fn shape(&self) -> Shape {
+		*self
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn circle(x: f32, y: f32, radius: f32) -> Circle {
+		Circle {
+			center: Vector { x, y },
+			radius,
+		}
+	}
+
+	#[test]
+	fn circles_intersect_through_the_trait() {
+		let colliders: [&dyn Collider; 3] = [
+			&circle(0.0, 0.0, 1.0),
+			&circle(1.5, 0.0, 1.0),
+			&circle(5.0, 0.0, 1.0),
+		];
+		assert!(colliders[0].intersects(colliders[1]));
+		assert!(colliders[1].intersects(colliders[0]));
+		assert!(!colliders[0].intersects(colliders[2]));
+		// Touching counts.
+		assert!(circle(0.0, 0.0, 1.0).intersects(&circle(2.0, 0.0, 1.0)));
+	}
+
+	#[test]
+	fn circle_and_box() {
+		let aabb = Aabb {
+			min: Vector { x: -1.0, y: -1.0 },
+			max: Vector { x: 1.0, y: 1.0 },
+		};
+		assert!(aabb.intersects(&circle(1.5, 0.0, 1.0)));
+		assert!(!aabb.intersects(&circle(1.5, 1.5, 0.5)));
+
+		let push = aabb.shape().separation(&circle(1.5, 0.0, 1.0)).unwrap();
+		assert_eq!(push, Vector { x: 0.5, y: 0.0 });
+		assert!(aabb.shape().separation(&circle(3.0, 0.0, 1.0)).is_none());
+	}
+}
@@ -0,0 +1,74 @@
+use crate::game::Ship;
+use crate::vector::Vector;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhysicsConfig {
+	// Low-pass filter on the propulsor acceleration: the fraction of the new input blended in
+	// on every update, in (0, 1]. `None` applies the input as is.
+	pub input_smoothing: Option<f32>,
+	// Speed a ship loses along its heading for every shot.
+	pub recoil: f32,
+	// Uniform acceleration applied to every ship and bullet.
+	pub gravity: Vector,
+	// Sustained fire heats ships up until they overheat. `None` lets ships fire freely.
+	pub heat: Option<HeatConfig>,
+	// Radians per second a ship can turn toward the orientation its inputs ask for. `None`
+	// turns instantly.
+	pub max_turn_rate: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeatConfig {
+	// Heat added by every bullet fired.
+	pub per_shot: f32,
+	// Heat at which the ship overheats and stops firing.
+	pub max: f32,
+	// Heat lost per second.
+	pub dissipation: f32,
+	// An overheated ship fires again once cooled down below this.
+	pub recover_below: f32,
+}
+
+impl Default for PhysicsConfig {
+	fn default() -> Self {
+		Self {
+			input_smoothing: None,
+			recoil: Ship::RECOIL,
+			gravity: Vector { x: 0.0, y: 0.0 },
+			heat: None,
+			max_turn_rate: None,
+		}
+	}
+}
+
+impl Default for HeatConfig {
+	fn default() -> Self {
+		Self {
+			per_shot: 1.0,
+			max: 10.0,
+			dissipation: 4.0,
+			recover_below: 5.0,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GameConfig {
+	// Simulation steps per second the server loop aims for.
+	pub tick_rate: f32,
+	// Seed of the game's random number generator, equal seeds give equal runs.
+	pub seed: u64,
+	// Seconds simulated by every step in fixed-step mode, whatever the real frame time.
+	// `None` simulates the measured time instead.
+	pub tick_dt: Option<f32>,
+}
+
+impl Default for GameConfig {
+	fn default() -> Self {
+		Self {
+			tick_rate: 60.0,
+			seed: 0,
+			tick_dt: None,
+		}
+	}
+}
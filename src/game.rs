@@ -0,0 +1,4150 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap};
+use std::io::{self, ErrorKind, Read, Write};
+use std::mem;
+use std::net::TcpStream;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::collider::{Circle, Collider};
+use crate::config::{GameConfig, HeatConfig, PhysicsConfig};
+use crate::grid::SpatialGrid;
+use crate::map::{Map, Obstacle};
+use crate::protocol::{
+	encode_header, is_known_protocol, peek_id, AckPacket, BulletState, ClientPacket, ControlPacket,
+	HeaderFormat, Packet, PacketBuf, PacketProtocol, PingPacket, PongPacket, ServerPacket,
+	StatsPacket, WelcomePacket, WorldSnapshot, HEADER_LEN, PRIORITY_LOW,
+};
+use crate::replay::ReplayRecorder;
+use crate::rng::Rng;
+use crate::tick::FixedStep;
+use crate::vector::{Vector, Vector64};
+
+/// Distinct id types, so a bullet id can never be passed where a ship id is expected:
+///
+/// ```compile_fail,E0308
+/// use ballgame::game::{BulletId, BulletKind, Game};
+///
+/// let mut game = Game::new();
+/// game.fire(BulletId(0), BulletKind::Standard);
+/// ```
+#[derive(
+	Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct ShipId(pub i32);
+
+#[derive(
+	Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct BulletId(pub i32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BulletKind {
+	Standard,
+	Fast,
+	Heavy,
+}
+
+impl BulletKind {
+	pub fn speed(&self) -> f32 {
+		match self {
+			BulletKind::Standard => 10.0,
+			BulletKind::Fast => 20.0,
+			BulletKind::Heavy => 6.0,
+		}
+	}
+
+	pub fn damage(&self) -> f32 {
+		match self {
+			BulletKind::Standard => 1.0,
+			BulletKind::Fast => 0.5,
+			BulletKind::Heavy => 3.0,
+		}
+	}
+}
+
+// Bullets fired per shot. With more than one, they fan out evenly around the ship's
+// orientation, `spread` radians apart.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Weapon {
+	pub kind: BulletKind,
+	pub count: u32,
+	pub spread: f32,
+	// Ships each bullet passes through before the next one stops it.
+	pub pierce: u8,
+}
+
+impl Weapon {
+	pub fn single(kind: BulletKind) -> Self {
+		Weapon {
+			kind,
+			count: 1,
+			spread: 0.0,
+			pierce: 0,
+		}
+	}
+
+	pub fn spread(kind: BulletKind, count: u32, spread: f32) -> Self {
+		Weapon {
+			kind,
+			count,
+			spread,
+			pierce: 0,
+		}
+	}
+
+	pub fn with_pierce(self, pierce: u8) -> Self {
+		Weapon { pierce, ..self }
+	}
+
+	// Angle of each bullet relative to the ship's orientation.
+	pub fn offsets(&self) -> impl Iterator<Item = f32> + '_ {
+		let center = (self.count as f32 - 1.0) / 2.0;
+		(0..self.count).map(move |index| (index as f32 - center) * self.spread)
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bullet {
+	pub id: BulletId,
+	pub position: Vector64,
+	pub velocity: Vector64,
+	pub owner_id: ShipId,
+	pub kind: BulletKind,
+	// Hits left before the bullet is consumed by the next one.
+	pub pierces_remaining: u8,
+	// Ships already hit during this flight, never hit twice.
+	pub hit_ships: Vec<ShipId>,
+}
+
+impl Bullet {
+	pub const RADIUS: f32 = 0.1;
+
+	pub fn collider(&self) -> Circle {
+		Circle {
+			center: self.position.into(),
+			radius: Self::RADIUS,
+		}
+	}
+
+	pub fn update(&mut self, dt: f32, physics: &PhysicsConfig) {
+		let dt = dt as f64;
+		self.velocity += Vector64::from(physics.gravity) * dt;
+		self.position += self.velocity * dt;
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Ship {
+	pub id: ShipId,
+	// Integrated in double precision, quantized to `f32` on the wire.
+	pub position: Vector64,
+	pub velocity: Vector64,
+	pub orientation: f32,
+	pub design: u8,
+	pub propulsor: [bool; 4],
+	pub can_shoot: i32,
+	pub hits: i32,
+	pub damage: f32,
+	pub max_health: f32,
+	pub radius: f32,
+	// Scales down thrust, recoil and knockback, and decides who gets pushed in ship collisions.
+	pub mass: f32,
+	pub team: u8,
+	// Bullets do not harm the ship before this tick, set when it spawns.
+	pub invulnerable_until_tick: u64,
+	// Whether that is still the case, refreshed by the game every step.
+	pub invulnerable: bool,
+	// Propulsor acceleration applied on the last update, after smoothing.
+	pub acceleration: Vector,
+	// Built up by firing when `PhysicsConfig::heat` is set, see `HeatConfig`.
+	pub heat: f32,
+	pub overheated: bool,
+	// Orientation the ship is turning toward under `PhysicsConfig::max_turn_rate`.
+	pub target_orientation: Option<f32>,
+}
+
+impl Ship {
+	pub const MAX_HEALTH: f32 = 10.0;
+	pub const THRUST: f32 = 1.0;
+	pub const RECOIL: f32 = 0.1;
+	pub const RADIUS: f32 = 1.0;
+	pub const MASS: f32 = 1.0;
+
+	// Direction the propulsors push in, one unit per axis, so diagonals are longer and
+	// opposite keys cancel out.
+	pub fn thrust_direction(&self) -> Vector {
+		let mut direction: Vector = [0.0, 0.0].into();
+
+		// WASD order.
+		if self.propulsor[0] {
+			direction.y += 1.0;
+		}
+		if self.propulsor[1] {
+			direction.x -= 1.0;
+		}
+		if self.propulsor[2] {
+			direction.y -= 1.0;
+		}
+		if self.propulsor[3] {
+			direction.x += 1.0;
+		}
+
+		direction
+	}
+
+	pub fn update(&mut self, dt: f32, physics: &PhysicsConfig) {
+		let mut acc = self.thrust_direction() * (Self::THRUST / self.mass);
+
+		if let Some(smoothing) = physics.input_smoothing {
+			acc = self.acceleration + (acc - self.acceleration) * smoothing;
+		}
+		self.acceleration = acc;
+		acc += physics.gravity;
+
+		if let Some(heat) = physics.heat.as_ref() {
+			self.cool_down(dt, heat);
+		}
+
+		if let (Some(rate), Some(target)) = (physics.max_turn_rate, self.target_orientation) {
+			// Shorter way around.
+			let difference = wrap_angle(target - self.orientation);
+			let turn = difference.clamp(-rate * dt, rate * dt);
+			self.orientation = wrap_angle(self.orientation + turn);
+			if turn == difference {
+				self.target_orientation = None;
+			}
+		}
+
+		// Update response.
+		let (dt, acc) = (dt as f64, Vector64::from(acc));
+		self.position += self.velocity * 0.5 * dt + acc * dt * dt;
+		self.velocity += acc * dt;
+	}
+
+	// Pushes the ship back, opposite to the direction it fires in.
+	pub fn shoot(&mut self, recoil: f32) {
+		self.velocity -=
+			Vector64::from(Vector::from_angle(self.orientation) * (recoil / self.mass));
+	}
+
+	pub fn heat_up(&mut self, amount: f32, heat: &HeatConfig) {
+		self.heat += amount;
+		if self.heat >= heat.max {
+			self.overheated = true;
+		}
+	}
+
+	pub fn cool_down(&mut self, dt: f32, heat: &HeatConfig) {
+		self.heat = (self.heat - heat.dissipation * dt).max(0.0);
+		if self.heat < heat.recover_below {
+			self.overheated = false;
+		}
+	}
+
+	pub fn receive_hit(&mut self, bullet: &Bullet) {
+		// Heavier hitting bullets knock the ship around more.
+		self.velocity += (0.1 * bullet.kind.damage() / self.mass) as f64 * bullet.velocity;
+		self.hits += 1;
+		self.take_damage(bullet.kind.damage());
+	}
+
+	pub fn take_damage(&mut self, amount: f32) {
+		self.damage += amount;
+	}
+
+	pub fn is_invulnerable(&self, tick: u64) -> bool {
+		tick < self.invulnerable_until_tick
+	}
+
+	pub fn is_destroyed(&self) -> bool {
+		self.damage >= self.max_health
+	}
+
+	// A destroyed ship stays around until the end of the step, it can no longer fire or steer.
+	pub fn is_alive(&self) -> bool {
+		!self.is_destroyed()
+	}
+
+	// Center and radius of the circle used for collisions.
+	pub fn bounding_circle(&self) -> (Vector, f32) {
+		(self.position.into(), self.radius)
+	}
+
+	pub fn collider(&self) -> Circle {
+		let (center, radius) = self.bounding_circle();
+		Circle { center, radius }
+	}
+}
+
+impl From<&Ship> for ServerPacket {
+	fn from(ship: &Ship) -> Self {
+		ServerPacket {
+			player_id: ship.id.0 as u32,
+			position: ship.position.into(),
+			velocity: ship.velocity.into(),
+			orientation: ship.orientation,
+			design: ship.design,
+			propulsor: pack_propulsor(ship.propulsor),
+			hits: ship.hits as u32,
+			invulnerable: ship.invulnerable,
+		}
+	}
+}
+
+impl From<&Bullet> for BulletState {
+	fn from(bullet: &Bullet) -> Self {
+		BulletState {
+			id: bullet.id.0 as u32,
+			position: bullet.position.into(),
+			velocity: bullet.velocity.into(),
+			owner_id: bullet.owner_id.0,
+		}
+	}
+}
+
+impl Default for Bullet {
+	fn default() -> Self {
+		Bullet {
+			id: BulletId(0),
+			position: Vector64::default(),
+			velocity: Vector64::default(),
+			owner_id: ShipId(0),
+			kind: BulletKind::Standard,
+			pierces_remaining: 0,
+			hit_ships: Vec::new(),
+		}
+	}
+}
+
+impl Default for Ship {
+	fn default() -> Self {
+		Ship {
+			id: ShipId(0),
+			position: Vector64::default(),
+			velocity: Vector64::default(),
+			orientation: 0.0,
+			design: 0,
+			propulsor: [false, false, false, false],
+			can_shoot: 0,
+			hits: 0,
+			damage: 0.0,
+			max_health: Ship::MAX_HEALTH,
+			radius: Ship::RADIUS,
+			mass: Ship::MASS,
+			team: 0,
+			invulnerable_until_tick: 0,
+			invulnerable: false,
+			acceleration: Vector { x: 0.0, y: 0.0 },
+			heat: 0.0,
+			overheated: false,
+			target_orientation: None,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClientData {
+	pub ship_id: ShipId,
+	pub orientation: f32,
+	pub propulsor: [bool; 4],
+	pub client_tick: u32,
+	pub sequence: u32,
+}
+
+impl From<&ClientPacket> for ClientData {
+	fn from(packet: &ClientPacket) -> Self {
+		ClientData {
+			ship_id: ShipId(packet.player_id as i32),
+			orientation: packet.orientation,
+			propulsor: unpack_propulsor(packet.propulsor),
+			client_tick: packet.client_tick,
+			sequence: packet.sequence,
+		}
+	}
+}
+
+impl From<&ClientData> for ClientPacket {
+	fn from(data: &ClientData) -> Self {
+		ClientPacket {
+			player_id: data.ship_id.0 as u32,
+			orientation: data.orientation,
+			propulsor: pack_propulsor(data.propulsor),
+			client_tick: data.client_tick,
+			sequence: data.sequence,
+		}
+	}
+}
+
+// Same angle within [-PI, PI).
+fn wrap_angle(radians: f32) -> f32 {
+	(radians + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU) - std::f32::consts::PI
+}
+
+// Propulsor bits on the wire, in WASD order from the lowest bit.
+pub fn pack_propulsor(propulsor: [bool; 4]) -> u8 {
+	let mut prop: u8 = 0;
+	if propulsor[0] {
+		prop |= 0b0001
+	}
+	if propulsor[1] {
+		prop |= 0b0010
+	}
+	if propulsor[2] {
+		prop |= 0b0100
+	}
+	if propulsor[3] {
+		prop |= 0b1000
+	}
+
+	prop
+}
+
+pub fn unpack_propulsor(propulsor: u8) -> [bool; 4] {
+	let pw = propulsor & 0b0001 != 0;
+	let pa = propulsor & 0b0010 != 0;
+	let ps = propulsor & 0b0100 != 0;
+	let pd = propulsor & 0b1000 != 0;
+
+	[pw, pa, ps, pd]
+}
+
+// Writes as much of `pending` as the writer accepts, keeping the rest for the next call so
+// a short write never splits a packet on the wire.
+pub fn write_pending<W: Write>(writer: &mut W, pending: &mut Vec<u8>) -> io::Result<()> {
+	let mut written = 0;
+	let result = loop {
+		if written == pending.len() {
+			break Ok(());
+		}
+
+		match writer.write(&pending[written..]) {
+			Ok(0) => break Err(ErrorKind::WriteZero.into()),
+			Ok(size) => written += size,
+			Err(error) if error.kind() == ErrorKind::Interrupted => {}
+			Err(error) if error.kind() == ErrorKind::WouldBlock => break Ok(()),
+			Err(error) => break Err(error),
+		}
+	};
+
+	pending.drain(0..written);
+	result
+}
+
+// Reads into `buffer`, retrying on interrupts. A reader with nothing to give right now reads
+// zero bytes instead of failing, a closed one fails with `UnexpectedEof`.
+pub fn read_available<R: Read>(reader: &mut R, buffer: &mut [u8]) -> io::Result<usize> {
+	loop {
+		match reader.read(buffer) {
+			Ok(0) if !buffer.is_empty() => {
+				break Err(io::Error::new(
+					ErrorKind::UnexpectedEof,
+					"Connection closed",
+				))
+			}
+			Ok(size) => break Ok(size),
+			Err(error) if error.kind() == ErrorKind::Interrupted => {}
+			Err(error) if error.kind() == ErrorKind::WouldBlock => break Ok(0),
+			Err(error) => break Err(error),
+		}
+	}
+}
+
+// Inputs received for a ship between steps, ordered by their client tick.
+#[derive(Default)]
+struct InputBuffer {
+	pending: BTreeMap<u32, ClientData>,
+	last_applied: Option<u32>,
+}
+
+impl InputBuffer {
+	// Only one input is applied per step, a client sending faster than the tick rate loses its
+	// oldest inputs instead of falling further behind.
+	const MAX_PENDING: usize = 8;
+
+	fn push(&mut self, input: ClientData) {
+		// Inputs that arrive after a newer one was applied are stale.
+		if self
+			.last_applied
+			.is_some_and(|last_applied| input.client_tick <= last_applied)
+		{
+			return;
+		}
+
+		self.pending.insert(input.client_tick, input);
+		if self.pending.len() > Self::MAX_PENDING {
+			self.pop();
+		}
+	}
+
+	fn pop(&mut self) -> Option<ClientData> {
+		let (client_tick, input) = self.pending.pop_first()?;
+		self.last_applied = Some(client_tick);
+
+		Some(input)
+	}
+}
+
+// Anything a player can be connected through: TCP, TLS over TCP, in-memory streams.
+pub trait PlayerStream: Read + Write + Send {}
+
+impl<S: Read + Write + Send> PlayerStream for S {}
+
+struct PlayerData {
+	stream: Box<dyn PlayerStream>,
+	ships: BTreeSet<ShipId>,
+	// Reassembles incoming packets, skipping junk between them.
+	buf: PacketBuf,
+	#[allow(dead_code)]
+	messages_received: i32,
+	// Sequence of the last input applied, echoed back for client reconciliation.
+	last_processed_seq: u32,
+	// Latest state tick the client acknowledged.
+	last_acked_tick: Option<u64>,
+	// Fully received messages waiting to be applied.
+	inbound: Vec<PacketProtocol<ClientPacket>>,
+	// Packets waiting to be sent, by priority then in queuing order.
+	queued: BinaryHeap<(u8, Reverse<u64>, Vec<u8>)>,
+	queued_count: u64,
+	// Bytes of the packets being sent that the socket did not accept yet.
+	outbound: Vec<u8>,
+}
+
+impl PlayerData {
+	fn new(stream: Box<dyn PlayerStream>) -> Self {
+		PlayerData {
+			stream,
+			ships: BTreeSet::new(),
+			buf: PacketBuf::new(),
+			messages_received: 0,
+			last_processed_seq: 0,
+			last_acked_tick: None,
+			inbound: Vec::new(),
+			queued: BinaryHeap::new(),
+			queued_count: 0,
+			outbound: Vec::new(),
+		}
+	}
+
+	// Low priority packets are periodic, a new one replaces any older packet of the same kind
+	// still waiting so a slow connection does not pile them up.
+	fn queue(&mut self, packet: &[u8], priority: u8) {
+		if priority == PRIORITY_LOW {
+			let id = peek_id(packet).ok();
+			self.queued
+				.retain(|(_, _, queued)| peek_id(queued).ok() != id);
+		}
+		self.queued
+			.push((priority, Reverse(self.queued_count), packet.to_vec()));
+		self.queued_count += 1;
+	}
+
+	// Sends queued packets, most important first. A packet is only picked once everything
+	// before it got through, so a partial write never interleaves two packets.
+	fn flush(&mut self) -> io::Result<()> {
+		loop {
+			write_pending(&mut self.stream, &mut self.outbound)?;
+			if !self.outbound.is_empty() {
+				return Ok(());
+			}
+
+			match self.queued.pop() {
+				Some((_, _, packet)) => self.outbound = packet,
+				None => return Ok(()),
+			}
+		}
+	}
+
+	// Reads at most this many buffers per poll, so a flooding client cannot stall the tick.
+	const MAX_READS: usize = 16;
+
+	// Reads what is available and returns every complete message, along with the number of
+	// headers skipped for announcing too much content. A corrupted stream only loses the
+	// bytes up to the next sync word, the connection is kept.
+	fn receive(&mut self) -> io::Result<(Vec<PacketProtocol<ClientPacket>>, u64)> {
+		let mut packets = Vec::new();
+		let mut skipped = 0;
+		let mut bytes = [0; 4096];
+		for _ in 0..Self::MAX_READS {
+			let size_read = read_available(&mut self.stream, &mut bytes)?;
+			let mut packet = self.buf.process(&bytes[..size_read]);
+			loop {
+				match packet {
+					Ok(Some(packet)) => packets.push(packet),
+					Ok(None) => break,
+					// The bad header is skipped, carry on with what follows it.
+					Err(_) => skipped += 1,
+				}
+				packet = self.buf.process(&[]);
+			}
+
+			if size_read < bytes.len() {
+				break;
+			}
+		}
+
+		Ok((packets, skipped))
+	}
+
+	// Every message is preceded by a `HeaderFormat::DEFAULT` header.
+
+	// Client Message, as a Zero protocol `ClientPacket`:
+	// [ 32 bits   |   32 bits   |  8 bits   |   32 bits   | 32 bits  ]
+	// [ player id | orientation | propulsor | client tick | sequence ]
+	fn read_client_message(packet: PacketProtocol<ClientPacket>) -> anyhow::Result<ClientData> {
+		Ok(ClientData::from(&packet.deserialize()?))
+	}
+}
+
+// What bullets do when they meet a ship that just spawned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvulnerabilityMode {
+	// Bullets fly through the ship.
+	PassThrough,
+	// Bullets are consumed, but the ship takes no damage or knockback.
+	Absorb,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoundsMode {
+	Unbounded,
+	// Stop entities against the walls.
+	Clamp,
+	// Entities leaving one side come back through the opposite one.
+	Wrap,
+}
+
+impl BoundsMode {
+	fn apply(
+		&self,
+		min: Vector64,
+		max: Vector64,
+		position: &mut Vector64,
+		velocity: &mut Vector64,
+	) {
+		match self {
+			BoundsMode::Unbounded => {}
+			BoundsMode::Clamp => {
+				let (x_clamped, y_clamped) = position.clamp_to_bounds(min, max);
+				if x_clamped {
+					*velocity = velocity.with_x(0.0);
+				}
+				if y_clamped {
+					*velocity = velocity.with_y(0.0);
+				}
+			}
+			BoundsMode::Wrap => {
+				position.x = min.x + (position.x - min.x).rem_euclid(max.x - min.x);
+				position.y = min.y + (position.y - min.y).rem_euclid(max.y - min.y);
+			}
+		}
+	}
+}
+
+// Circular safe zone that contracts over time, ships outside of it take damage.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SafeZone {
+	pub center: Vector,
+	pub radius: f32,
+	pub min_radius: f32,
+	// Radius lost per second.
+	pub shrink_rate: f32,
+	// Damage per second taken outside of the zone.
+	pub damage_rate: f32,
+}
+
+impl SafeZone {
+	pub fn contains(&self, position: Vector) -> bool {
+		(position - self.center).length() <= self.radius
+	}
+
+	fn shrink(&mut self, dt: f32) {
+		self.radius = (self.radius - self.shrink_rate * dt).max(self.min_radius);
+	}
+}
+
+// Entities removed during a step, so clients can stop rendering them. Ships given up since the
+// previous step are included.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RemovedEntities {
+	pub ships: Vec<ShipId>,
+	pub bullets: Vec<BulletId>,
+}
+
+// What reading from the players did during one `net_poll` call.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TickReport {
+	// Indices the failed players had when the call started, they are removed since.
+	pub disconnected: Vec<usize>,
+	// Ships of the disconnected players, removed along with them.
+	pub removed_ships: Vec<ShipId>,
+	// Messages fully received across all players.
+	pub packets_received: usize,
+}
+
+// Counters describing the game right now.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct GameMetrics {
+	pub players: usize,
+	pub ships: usize,
+	pub bullets: usize,
+	pub tick: u64,
+	// Wall clock time the last step took.
+	pub step_time: Duration,
+}
+
+impl From<&GameMetrics> for StatsPacket {
+	fn from(metrics: &GameMetrics) -> Self {
+		StatsPacket {
+			players: metrics.players as u32,
+			ships: metrics.ships as u32,
+			bullets: metrics.bullets as u32,
+			tick: metrics.tick,
+			tick_ms: metrics.step_time.as_secs_f32() * 1000.0,
+		}
+	}
+}
+
+pub struct Game {
+	// Player data.
+	ships: Vec<Ship>,
+	players: Vec<PlayerData>,
+	bullets: Vec<Bullet>,
+	inputs: HashMap<ShipId, InputBuffer>,
+	next_ship_id: i32,
+	next_bullet_id: i32,
+	// Steps run so far, tags recorded packets.
+	tick: u64,
+
+	// World data, centered on the origin.
+	world_width: f32,
+	world_height: f32,
+	bounds_mode: BoundsMode,
+	safe_zone: Option<SafeZone>,
+	obstacles: Vec<Obstacle>,
+	tick_rate: f32,
+
+	// Rules.
+	physics: PhysicsConfig,
+	bullet_collision: bool,
+	ship_collision: bool,
+	max_bullets_per_ship: Option<usize>,
+
+	// Ships driven by the server, with the time left until each can fire again.
+	bots: BTreeMap<ShipId, f32>,
+	bot_ai: bool,
+
+	// Players only receive the entities this close to one of their ships.
+	view_radius: Option<f32>,
+	// State goes out every this many ticks, counted by `ticks_since_broadcast`.
+	broadcast_interval: u64,
+	ticks_since_broadcast: u64,
+
+	// Streams accepted on another thread, waiting to become players.
+	connections: Option<Receiver<TcpStream>>,
+
+	// Teams new players are spread over, none when zero.
+	team_count: u8,
+	// Otherwise players wait for a `ControlPacket::RequestSpawn` to get a ship.
+	spawn_on_connect: bool,
+	// Ships given up through `ControlPacket::Leave`, reported by the next step.
+	left_ships: Vec<ShipId>,
+	// Ships spawn anywhere in the world instead of at its center.
+	random_spawns: bool,
+
+	rng: Rng,
+
+	// Ticks a ship stays invulnerable after spawning, and what that does to bullets.
+	spawn_invulnerability: u64,
+	invulnerability_mode: InvulnerabilityMode,
+
+	// Set in fixed-step mode, turns frame times into steps of a constant dt.
+	fixed_step: Option<FixedStep>,
+	// Wall clock time the last step took, see `metrics`.
+	last_step_time: Duration,
+
+	// Receives every packet `net_poll` reads, see `set_recorder`.
+	recorder: Option<ReplayRecorder>,
+
+	// Problems with clients that the server recovers from, for operators to monitor.
+	malformed_packets: u64,
+	dropped_connections: u64,
+	rejected_inputs: u64,
+}
+
+impl Game {
+	pub const DEFAULT_WORLD_WIDTH: f32 = 200.0;
+	pub const DEFAULT_WORLD_HEIGHT: f32 = 200.0;
+	const GRID_CELL_SIZE: f32 = 4.0;
+	// Seconds.
+	const SPAWN_INVULNERABILITY: f32 = 2.0;
+
+	// Bot behaviour.
+	const BOT_TURN_RATE: f32 = std::f32::consts::PI;
+	const BOT_AIM_TOLERANCE: f32 = 0.1;
+	const BOT_FIRE_INTERVAL: f32 = 0.5;
+
+	// Bytes taken by each part of the binary server message.
+	const MESSAGE_PREFIX_SIZE: usize = 8 + 4 + 4 + 4;
+	const SHIP_WIRE_SIZE: usize = 4 + 4 * 4 + 4 + 1 + 1 + 4 + 1;
+	const BULLET_WIRE_SIZE: usize = 4 + 4 * 4 + 4;
+
+	pub fn new() -> Self {
+		Self::with_config(GameConfig::default()).expect("the default config is valid")
+	}
+
+	pub fn with_config(config: GameConfig) -> anyhow::Result<Self> {
+		if !(config.tick_rate.is_finite() && config.tick_rate > 0.0) {
+			anyhow::bail!("Invalid tick rate {}", config.tick_rate);
+		}
+		if let Some(dt) = config.tick_dt.filter(|dt| !(dt.is_finite() && *dt > 0.0)) {
+			anyhow::bail!("Invalid tick dt {dt}");
+		}
+
+		Ok(Game {
+			ships: Vec::new(),
+			players: Vec::new(),
+			bullets: Vec::new(),
+			inputs: HashMap::new(),
+			next_ship_id: 0,
+			next_bullet_id: 0,
+			tick: 0,
+			world_width: Self::DEFAULT_WORLD_WIDTH,
+			world_height: Self::DEFAULT_WORLD_HEIGHT,
+			bounds_mode: BoundsMode::Unbounded,
+			safe_zone: None,
+			obstacles: Vec::new(),
+			tick_rate: config.tick_rate,
+			physics: PhysicsConfig::default(),
+			bullet_collision: false,
+			ship_collision: false,
+			max_bullets_per_ship: None,
+			bots: BTreeMap::new(),
+			bot_ai: true,
+			view_radius: None,
+			broadcast_interval: 1,
+			ticks_since_broadcast: 0,
+			connections: None,
+			team_count: 0,
+			spawn_on_connect: true,
+			left_ships: Vec::new(),
+			random_spawns: false,
+			rng: Rng::new(config.seed),
+			spawn_invulnerability: (Self::SPAWN_INVULNERABILITY * config.tick_rate) as u64,
+			invulnerability_mode: InvulnerabilityMode::PassThrough,
+			fixed_step: config.tick_dt.map(FixedStep::new),
+			last_step_time: Duration::ZERO,
+			recorder: None,
+			malformed_packets: 0,
+			dropped_connections: 0,
+			rejected_inputs: 0,
+		})
+	}
+
+	pub fn bounds_mode(&self) -> BoundsMode {
+		self.bounds_mode
+	}
+
+	pub fn set_bounds_mode(&mut self, mode: BoundsMode) {
+		self.bounds_mode = mode;
+	}
+
+	// Resizes the world, pulling every entity back in according to the bounds mode. Both sides
+	// must be positive and finite.
+	pub fn set_world_size(&mut self, width: f32, height: f32) -> anyhow::Result<()> {
+		let valid = |side: f32| side.is_finite() && side > 0.0;
+		if !valid(width) || !valid(height) {
+			anyhow::bail!("Invalid world size {width}x{height}");
+		}
+
+		self.world_width = width;
+		self.world_height = height;
+		self.apply_bounds();
+		Ok(())
+	}
+
+	// Records every packet read from now on, tagged with the tick and the player index, to be
+	// replayed by a `ReplayPlayer`.
+	pub fn set_recorder(&mut self, recorder: Option<ReplayRecorder>) {
+		self.recorder = recorder;
+	}
+
+	pub fn take_recorder(&mut self) -> Option<ReplayRecorder> {
+		self.recorder.take()
+	}
+
+	pub fn physics(&self) -> &PhysicsConfig {
+		&self.physics
+	}
+
+	pub fn set_physics(&mut self, physics: PhysicsConfig) {
+		self.physics = physics;
+	}
+
+	// When enabled, bullets from different owners cancel each other out.
+	pub fn set_bullet_collision(&mut self, enabled: bool) {
+		self.bullet_collision = enabled;
+	}
+
+	// When enabled, ships spawn and respawn at random points drawn from the game's seeded rng.
+	pub fn set_random_spawns(&mut self, enabled: bool) {
+		self.random_spawns = enabled;
+	}
+
+	// Ticks new and reset ships ignore bullets for, two seconds' worth by default.
+	pub fn set_spawn_invulnerability(&mut self, ticks: u64) {
+		self.spawn_invulnerability = ticks;
+	}
+
+	pub fn set_invulnerability_mode(&mut self, mode: InvulnerabilityMode) {
+		self.invulnerability_mode = mode;
+	}
+
+	// When enabled, overlapping ships bounce off each other, the lighter one taking more of it.
+	pub fn set_ship_collision(&mut self, enabled: bool) {
+		self.ship_collision = enabled;
+	}
+
+	// When disabled, new players join without a ship and spawn through a
+	// `ControlPacket::RequestSpawn`.
+	pub fn set_spawn_on_connect(&mut self, enabled: bool) {
+		self.spawn_on_connect = enabled;
+	}
+
+	// With a radius, each broadcast only carries what is near the player's own ships.
+	pub fn set_view_radius(&mut self, radius: Option<f32>) {
+		self.view_radius = radius;
+	}
+
+	// Broadcasts state every `ticks` ticks, clients interpolate in between. Zero counts as one.
+	pub fn set_broadcast_interval(&mut self, ticks: u64) {
+		self.broadcast_interval = ticks.max(1);
+	}
+
+	// Whether the next `broadcast_state` call sends anything.
+	pub fn broadcast_due(&self) -> bool {
+		self.broadcast_interval == 1 || self.ticks_since_broadcast >= self.broadcast_interval
+	}
+
+	// Limits how many bullets each ship can have in flight, shots over the limit are refused.
+	pub fn set_max_bullets_per_ship(&mut self, max: Option<usize>) {
+		self.max_bullets_per_ship = max;
+	}
+
+	// Replaces the obstacles with the map's.
+	pub fn load_map(&mut self, map: Map) {
+		self.obstacles = map.obstacles;
+	}
+
+	pub fn obstacles(&self) -> &[Obstacle] {
+		&self.obstacles
+	}
+
+	pub fn safe_zone(&self) -> Option<&SafeZone> {
+		self.safe_zone.as_ref()
+	}
+
+	pub fn set_safe_zone(&mut self, safe_zone: Option<SafeZone>) {
+		self.safe_zone = safe_zone;
+	}
+
+	pub fn world_bounds(&self) -> (Vector, Vector) {
+		let half = Vector {
+			x: self.world_width / 2.0,
+			y: self.world_height / 2.0,
+		};
+
+		(-half, half)
+	}
+
+	// Number of simulation steps run so far.
+	pub fn tick(&self) -> u64 {
+		self.tick
+	}
+
+	// No one is connected, the server loop can stop stepping until someone joins.
+	pub fn is_idle(&self) -> bool {
+		self.players.is_empty()
+	}
+
+	// Index of the player controlling the ship, `None` for bots and unowned ships.
+	pub fn player_for_ship(&self, ship_id: ShipId) -> Option<usize> {
+		self.players
+			.iter()
+			.position(|player| player.ships.contains(&ship_id))
+	}
+
+	pub fn players_len(&self) -> usize {
+		self.players.len()
+	}
+
+	pub fn ships_len(&self) -> usize {
+		self.ships.len()
+	}
+
+	// Packets dropped because they could not be understood or were not the client's to send.
+	pub fn malformed_packets(&self) -> u64 {
+		self.malformed_packets
+	}
+
+	// Connections lost because writing to or reading from them failed.
+	pub fn dropped_connections(&self) -> u64 {
+		self.dropped_connections
+	}
+
+	pub fn ships(&self) -> &[Ship] {
+		&self.ships
+	}
+
+	pub fn bullets(&self) -> &[Bullet] {
+		&self.bullets
+	}
+
+	pub fn ship(&self, id: ShipId) -> Option<&Ship> {
+		self.ships.iter().find(|ship| ship.id == id)
+	}
+
+	pub fn bullet(&self, id: BulletId) -> Option<&Bullet> {
+		self.bullets.iter().find(|bullet| bullet.id == id)
+	}
+
+	// Removes the bullet, returning whether it existed.
+	pub fn despawn_bullet(&mut self, id: BulletId) -> bool {
+		match self.bullets.iter().position(|bullet| bullet.id == id) {
+			Some(index) => {
+				self.bullets.remove(index);
+				true
+			}
+			None => false,
+		}
+	}
+
+	// The ship as clients see it now. Same as `ServerPacket::from`, but does not rely on the
+	// ship's `invulnerable` flag being refreshed yet.
+	pub fn ship_packet(&self, ship: &Ship) -> ServerPacket {
+		ServerPacket {
+			invulnerable: ship.is_invulnerable(self.tick),
+			..ServerPacket::from(ship)
+		}
+	}
+
+	pub fn snapshot(&self) -> WorldSnapshot {
+		WorldSnapshot {
+			ships: self
+				.ships
+				.iter()
+				.map(|ship| self.ship_packet(ship))
+				.collect(),
+			bullets: self.bullets.iter().map(BulletState::from).collect(),
+			tick: self.tick,
+		}
+	}
+
+	// Connections the accept thread can queue before it blocks on the game loop.
+	pub const DEFAULT_PENDING_CONNECTIONS: usize = 16;
+
+	// Returns the sender an accept thread hands its streams to, they join on the next
+	// `accept_pending`. Replaces any previous channel.
+	pub fn connection_channel(&mut self, capacity: usize) -> SyncSender<TcpStream> {
+		let (sender, receiver) = mpsc::sync_channel(capacity);
+		self.connections = Some(receiver);
+		sender
+	}
+
+	// Turns every stream waiting in the connection channel into a non-blocking player, returning
+	// the ids of their ships. Streams that fail to set up or receive the welcome are dropped.
+	pub fn accept_pending(&mut self) -> Vec<ShipId> {
+		let mut streams = Vec::new();
+		if let Some(connections) = self.connections.as_ref() {
+			streams.extend(connections.try_iter());
+		}
+
+		streams
+			.into_iter()
+			.filter(|stream| stream.set_nonblocking(true).is_ok())
+			.filter_map(|stream| self.new_player(stream).ok().flatten())
+			.collect()
+	}
+
+	// Adds the player and, unless spawning on connect is disabled, spawns its ship and returns
+	// the id so the client knows which one to control.
+	pub fn new_player(
+		&mut self,
+		new_stream: impl PlayerStream + 'static,
+	) -> anyhow::Result<Option<ShipId>> {
+		self.players.push(PlayerData::new(Box::new(new_stream)));
+		if !self.spawn_on_connect {
+			return Ok(None);
+		}
+
+		let index = self.players.len() - 1;
+		match self.spawn_for_player(index, 0, None) {
+			Ok(id) => Ok(Some(id)),
+			Err(error) => {
+				// A client that cannot take its welcome is gone already.
+				self.players.pop();
+				if error.is::<io::Error>() {
+					self.dropped_connections += 1;
+				}
+				Err(error)
+			}
+		}
+	}
+
+	// Spawns a ship controlled by the player. Without a team the suggested one is used. The
+	// client is greeted with a `WelcomePacket` for the ship before its state is sent.
+	fn spawn_for_player(
+		&mut self,
+		player_index: usize,
+		design: u8,
+		team: Option<u8>,
+	) -> anyhow::Result<ShipId> {
+		if let Some(team) = team {
+			if team >= self.team_count {
+				anyhow::bail!("No team {team}");
+			}
+		}
+
+		let welcome =
+			PacketProtocol::Zero(self.welcome_packet(ShipId(self.next_ship_id))).serialize()?;
+		let Some(player) = self.players.get_mut(player_index) else {
+			anyhow::bail!("No player {player_index}");
+		};
+		player.queue(&welcome, WelcomePacket::priority());
+		player.flush()?;
+
+		let team = team.or(self.suggest_team());
+		let id = self.spawn_ship();
+		if let Some(ship) = self.ships.iter_mut().find(|ship| ship.id == id) {
+			ship.design = design;
+			if let Some(team) = team {
+				ship.team = team;
+			}
+		}
+		self.players[player_index].ships.insert(id);
+
+		Ok(id)
+	}
+
+	// With a count, new players join the team with the fewest ships.
+	pub fn set_team_count(&mut self, count: u8) {
+		self.team_count = count;
+	}
+
+	// Ships on each team, teams without ships included.
+	pub fn ship_count_by_team(&self) -> HashMap<u8, usize> {
+		let mut counts: HashMap<u8, usize> = (0..self.team_count).map(|team| (team, 0)).collect();
+		for ship in self.ships.iter() {
+			*counts.entry(ship.team).or_default() += 1;
+		}
+
+		counts
+	}
+
+	// Least populated team, the lowest one on ties. `None` without teams.
+	pub fn suggest_team(&self) -> Option<u8> {
+		let counts = self.ship_count_by_team();
+		(0..self.team_count).min_by_key(|team| counts[team])
+	}
+
+	// Spawns a ship that is not controlled by any connection.
+	pub fn spawn_ship(&mut self) -> ShipId {
+		let id = ShipId(self.next_ship_id);
+		self.next_ship_id += 1;
+		let position = self.next_spawn_point().into();
+		self.ships.push(Ship {
+			id,
+			position,
+			invulnerable_until_tick: self.tick + self.spawn_invulnerability,
+			invulnerable: self.spawn_invulnerability > 0,
+			..Default::default()
+		});
+
+		id
+	}
+
+	// Where new ships appear, the center of the world.
+	pub fn spawn_point(&self) -> Vector {
+		Vector { x: 0.0, y: 0.0 }
+	}
+
+	// Where the next ship appears, depending on whether random spawns are enabled.
+	fn next_spawn_point(&mut self) -> Vector {
+		if self.random_spawns {
+			self.random_spawn_point()
+		} else {
+			self.spawn_point()
+		}
+	}
+
+	// Source of all the game's randomness, seeded from `GameConfig::seed`.
+	pub fn rng(&mut self) -> &mut Rng {
+		&mut self.rng
+	}
+
+	// Uniformly random point inside the world bounds.
+	pub fn random_spawn_point(&mut self) -> Vector {
+		let (min, max) = self.world_bounds();
+		Vector {
+			x: self.rng.range(min.x, max.x),
+			y: self.rng.range(min.y, max.y),
+		}
+	}
+
+	// Starts a new round without dropping anyone: bullets and pending inputs are cleared and every
+	// ship respawns at rest and undamaged. Hits are the ships' score, they are only zeroed with
+	// `reset_scores`.
+	pub fn reset_round(&mut self, reset_scores: bool) {
+		self.bullets.clear();
+		for index in 0..self.ships.len() {
+			let spawn = self.next_spawn_point().into();
+			let ship = &mut self.ships[index];
+			ship.position = spawn;
+			ship.velocity = Vector64::default();
+			ship.acceleration = Vector::default();
+			ship.damage = 0.0;
+			if reset_scores {
+				ship.hits = 0;
+			}
+
+			ship.heat = 0.0;
+			ship.overheated = false;
+			ship.target_orientation = None;
+			ship.invulnerable_until_tick = self.tick + self.spawn_invulnerability;
+			ship.invulnerable = self.spawn_invulnerability > 0;
+		}
+		for cooldown in self.bots.values_mut() {
+			*cooldown = 0.0;
+		}
+		// Inputs sent during the old round must not move ships in the new one.
+		self.inputs.clear();
+		for player in self.players.iter_mut() {
+			player.inbound.clear();
+		}
+	}
+
+	pub fn welcome_packet(&self, ship_id: ShipId) -> WelcomePacket {
+		WelcomePacket {
+			your_ship_id: ship_id.0 as u32,
+			world_width: self.world_width,
+			world_height: self.world_height,
+			tick_rate: self.tick_rate,
+			thrust: Ship::THRUST,
+			recoil: self.physics.recoil,
+		}
+	}
+
+	// Spawns a ship controlled by the server instead of a player.
+	pub fn add_bot(&mut self) -> ShipId {
+		let id = self.spawn_ship();
+		self.bots.insert(id, 0.0);
+		id
+	}
+
+	pub fn is_bot(&self, ship_id: ShipId) -> bool {
+		self.bots.contains_key(&ship_id)
+	}
+
+	// While disabled, bots keep their last orientation and stop firing.
+	pub fn set_bot_ai(&mut self, enabled: bool) {
+		self.bot_ai = enabled;
+	}
+
+	// Turns every bot toward the nearest enemy ship and fires once it is aimed close enough.
+	pub fn update_bots(&mut self, dt: f32) {
+		let bots: Vec<ShipId> = self.bots.keys().copied().collect();
+		for id in bots {
+			let Some(bot) = self.ships.iter().find(|ship| ship.id == id) else {
+				continue;
+			};
+			let Some(target) = self.nearest_enemy(bot) else {
+				continue;
+			};
+
+			let difference = Vector::from_angle(bot.orientation)
+				.angle_between((target.position - bot.position).into());
+			let turn = difference.clamp(-Self::BOT_TURN_RATE * dt, Self::BOT_TURN_RATE * dt);
+
+			if let Some(bot) = self.ships.iter_mut().find(|ship| ship.id == id) {
+				bot.orientation += turn;
+			}
+
+			let cooldown = self.bots.entry(id).or_default();
+			*cooldown = (*cooldown - dt).max(0.0);
+			if *cooldown == 0.0 && (difference - turn).abs() <= Self::BOT_AIM_TOLERANCE {
+				*cooldown = Self::BOT_FIRE_INTERVAL;
+				self.fire(id, BulletKind::Standard);
+			}
+		}
+	}
+
+	// Closest ship to `position`, skipping the ship with the `exclude` id.
+	pub fn nearest_ship(&self, position: Vector, exclude: Option<ShipId>) -> Option<&Ship> {
+		let position = Vector64::from(position);
+		self.ships
+			.iter()
+			.filter(|ship| Some(ship.id) != exclude)
+			.min_by(|a, b| {
+				let a = a.position.distance_squared(position);
+				let b = b.position.distance_squared(position);
+				a.total_cmp(&b)
+			})
+	}
+
+	// Closest ship on another team than `ship`, or closest other ship when there are no teams.
+	pub fn nearest_enemy(&self, ship: &Ship) -> Option<&Ship> {
+		self.ships
+			.iter()
+			.filter(|other| other.id != ship.id)
+			.filter(|other| self.team_count == 0 || other.team != ship.team)
+			.min_by(|a, b| {
+				let a = a.position.distance_squared(ship.position);
+				let b = b.position.distance_squared(ship.position);
+				a.total_cmp(&b)
+			})
+	}
+
+	// Ships whose position lies inside the box from `min` to `max`, for culling and rectangular
+	// views.
+	pub fn ships_in_aabb(&self, min: Vector, max: Vector) -> Vec<ShipId> {
+		// A single pass, building a grid for one query would cost more than it saves.
+		self.ships
+			.iter()
+			.filter(|ship| {
+				let position = Vector::from(ship.position);
+				(min.x..=max.x).contains(&position.x) && (min.y..=max.y).contains(&position.y)
+			})
+			.map(|ship| ship.id)
+			.collect()
+	}
+
+	// Fires a bullet of the given kind from the ship's nose, returning its id.
+	pub fn fire(&mut self, ship_id: ShipId, kind: BulletKind) -> Option<BulletId> {
+		self.fire_weapon(ship_id, &Weapon::single(kind)).pop()
+	}
+
+	// Fires every bullet of the weapon at once, returning their ids. Nothing is fired when the
+	// whole volley does not fit under the per ship bullet limit or the ship is overheated.
+	pub fn fire_weapon(&mut self, ship_id: ShipId, weapon: &Weapon) -> Vec<BulletId> {
+		if !self.ship(ship_id).is_some_and(Ship::is_alive) {
+			return Vec::new();
+		}
+
+		let in_flight = self
+			.bullets
+			.iter()
+			.filter(|bullet| bullet.owner_id == ship_id)
+			.count();
+		if let Some(max) = self.max_bullets_per_ship {
+			if in_flight + weapon.count as usize > max {
+				return Vec::new();
+			}
+		}
+
+		let Some(ship) = self.ships.iter_mut().find(|ship| ship.id == ship_id) else {
+			return Vec::new();
+		};
+		if let Some(heat) = self.physics.heat.as_ref() {
+			if ship.overheated {
+				return Vec::new();
+			}
+			ship.heat_up(heat.per_shot * weapon.count as f32, heat);
+		}
+		ship.shoot(self.physics.recoil);
+
+		let heading = Vector::from_angle(ship.orientation);
+		let mut ids = Vec::with_capacity(weapon.count as usize);
+		for offset in weapon.offsets() {
+			let id = BulletId(self.next_bullet_id);
+			self.next_bullet_id += 1;
+			self.bullets.push(Bullet {
+				id,
+				position: ship.position,
+				velocity: ship.velocity
+					+ Vector64::from(heading.rotate(offset) * weapon.kind.speed()),
+				owner_id: ship.id,
+				kind: weapon.kind,
+				pierces_remaining: weapon.pierce,
+				hit_ships: Vec::new(),
+			});
+			ids.push(id);
+		}
+
+		ids
+	}
+
+	// Buffers an input to be applied on the next step, in client tick order.
+	pub fn queue_input(&mut self, input: ClientData) {
+		self.inputs.entry(input.ship_id).or_default().push(input);
+	}
+
+	// Steers the ship with the input, returning whether it was applied. Inputs with a non finite
+	// orientation are rejected and counted, valid ones have their orientation wrapped into
+	// [-PI, PI). With a maximum turn rate the ship turns toward it over the next updates.
+	// Propulsor bits above the low four never make it out of `unpack_propulsor`.
+	pub fn apply_input(&mut self, input: ClientData) -> bool {
+		if !input.orientation.is_finite() {
+			self.rejected_inputs += 1;
+			return false;
+		}
+
+		let Some(ship) = self.ships.iter_mut().find(|ship| ship.id == input.ship_id) else {
+			return false;
+		};
+		if !ship.is_alive() {
+			return false;
+		}
+		let orientation = wrap_angle(input.orientation);
+		if self.physics.max_turn_rate.is_some() {
+			ship.target_orientation = Some(orientation);
+		} else {
+			ship.orientation = orientation;
+		}
+		ship.propulsor = input.propulsor;
+
+		true
+	}
+
+	// Inputs thrown away by `apply_input` for being invalid.
+	pub fn rejected_inputs(&self) -> u64 {
+		self.rejected_inputs
+	}
+
+	// Applies inputs received since the last call: messages fully read from each player and
+	// anything queued through `queue_input`. One input per ship, inputs that arrived together
+	// each get a step of their own. Returns how many inputs were applied.
+	pub fn apply_all_inputs(&mut self) -> usize {
+		let mut received = Vec::new();
+		for (index, player) in self.players.iter_mut().enumerate() {
+			received.extend(player.inbound.drain(..).map(|packet| (index, packet)));
+		}
+		for (index, packet) in received {
+			// A malformed or unexpected packet only loses itself. Failing to answer a ping is
+			// the connection's problem, the next broadcast notices it.
+			match self.handle_packet(index, packet) {
+				Err(error) if !error.is::<io::Error>() => self.malformed_packets += 1,
+				_ => {}
+			}
+		}
+
+		let inputs: Vec<ClientData> = self
+			.inputs
+			.values_mut()
+			.filter_map(InputBuffer::pop)
+			.collect();
+
+		let mut applied = 0;
+		for input in inputs {
+			if let Some(player) = self
+				.players
+				.iter_mut()
+				.find(|player| player.ships.contains(&input.ship_id))
+			{
+				player.last_processed_seq = input.sequence;
+			}
+
+			if self.apply_input(input) {
+				applied += 1;
+			}
+		}
+
+		applied
+	}
+
+	// Routes a received packet by its id. Inputs are queued to be applied in client tick order
+	// by `apply_all_inputs`, pings are answered with a pong, acks are recorded and control
+	// packets spawn or remove the player's ships.
+	pub fn handle_packet<T: Packet>(
+		&mut self,
+		player_index: usize,
+		packet: PacketProtocol<T>,
+	) -> anyhow::Result<()> {
+		let (id, protocol, content) = match packet.into_raw()? {
+			PacketProtocol::Raw {
+				id,
+				protocol,
+				content,
+			} => (id, protocol, content),
+			_ => anyhow::bail!("Packet {} could not be encoded", T::id()),
+		};
+		let Some(player) = self.players.get_mut(player_index) else {
+			anyhow::bail!("No player {player_index}");
+		};
+
+		if id == ClientPacket::id() {
+			let input = PlayerData::read_client_message(PacketProtocol::Raw {
+				id,
+				protocol,
+				content,
+			})?;
+
+			// Clients may only steer their own ships.
+			if !player.ships.contains(&input.ship_id) {
+				anyhow::bail!("Ship {} is not controlled by the player", input.ship_id.0);
+			}
+			self.queue_input(input);
+		} else if id == PingPacket::id() {
+			let ping = PacketProtocol::<PingPacket>::Raw {
+				id,
+				protocol,
+				content,
+			};
+			let pong = ping.map(|ping| PongPacket {
+				client_time: ping.client_time,
+			})?;
+
+			player.queue(&pong.serialize()?, PongPacket::priority());
+			player.flush()?;
+		} else if id == AckPacket::id() {
+			let ack = PacketProtocol::<AckPacket>::Raw {
+				id,
+				protocol,
+				content,
+			}
+			.deserialize()?;
+
+			player.last_acked_tick = player.last_acked_tick.max(Some(ack.tick));
+		} else if id == ControlPacket::id() {
+			let control = PacketProtocol::<ControlPacket>::Raw {
+				id,
+				protocol,
+				content,
+			}
+			.deserialize()?;
+
+			match control {
+				ControlPacket::RequestSpawn { design, team } => {
+					if !player.ships.is_empty() {
+						anyhow::bail!("Player {player_index} already has a ship");
+					}
+					self.spawn_for_player(player_index, design, team)?;
+				}
+				ControlPacket::Leave => {
+					let ships = mem::take(&mut player.ships);
+					self.ships.retain(|ship| !ships.contains(&ship.id));
+					for id in ships.iter() {
+						self.inputs.remove(id);
+						self.bots.remove(id);
+					}
+					self.left_ships.extend(ships);
+				}
+			}
+		} else {
+			anyhow::bail!("Unexpected packet id {id}");
+		}
+
+		Ok(())
+	}
+
+	// Latest state tick the player acknowledged, if any.
+	pub fn last_acked_tick(&self, player_index: usize) -> Option<u64> {
+		self.players.get(player_index)?.last_acked_tick
+	}
+
+	// Seconds every step simulates in fixed-step mode.
+	pub fn tick_dt(&self) -> Option<f32> {
+		self.fixed_step.as_ref().map(FixedStep::dt)
+	}
+
+	// Advances the simulation by a frame of `real_dt` seconds. In fixed-step mode this runs as
+	// many `tick_dt` steps as the frame covers, otherwise a single step of `real_dt`.
+	pub fn advance(&mut self, real_dt: f32) -> RemovedEntities {
+		let Some(mut fixed_step) = self.fixed_step.take() else {
+			return self.step(real_dt);
+		};
+
+		let mut removed = RemovedEntities::default();
+		fixed_step.advance(real_dt, |dt| {
+			let step = self.step(dt);
+			removed.ships.extend(step.ships);
+			removed.bullets.extend(step.bullets);
+		});
+		self.fixed_step = Some(fixed_step);
+
+		removed
+	}
+
+	// Advances the simulation by `dt` seconds, with whatever inputs were applied so far.
+	pub fn step(&mut self, dt: f32) -> RemovedEntities {
+		let started = Instant::now();
+		let removed = self.simulate(dt);
+		self.last_step_time = started.elapsed();
+
+		removed
+	}
+
+	pub fn metrics(&self) -> GameMetrics {
+		GameMetrics {
+			players: self.players.len(),
+			ships: self.ships.len(),
+			bullets: self.bullets.len(),
+			tick: self.tick,
+			step_time: self.last_step_time,
+		}
+	}
+
+	// Sends the current metrics to the connection at `player_index`.
+	pub fn send_stats(&mut self, player_index: usize) -> anyhow::Result<()> {
+		let packet = PacketProtocol::Zero(StatsPacket::from(&self.metrics())).serialize()?;
+		let Some(player) = self.players.get_mut(player_index) else {
+			anyhow::bail!("No player {player_index}");
+		};
+
+		player.queue(&packet, StatsPacket::priority());
+		player.flush()?;
+		Ok(())
+	}
+
+	fn simulate(&mut self, dt: f32) -> RemovedEntities {
+		let mut removed = RemovedEntities {
+			ships: mem::take(&mut self.left_ships),
+			..Default::default()
+		};
+		self.tick += 1;
+		self.ticks_since_broadcast += 1;
+
+		for ship in self.ships.iter_mut() {
+			ship.invulnerable = ship.is_invulnerable(self.tick);
+		}
+
+		// Nothing to simulate, skip the rest of the step. The safe zone waits as well.
+		if self.ships.is_empty() && self.bullets.is_empty() {
+			return removed;
+		}
+
+		if self.bot_ai {
+			self.update_bots(dt);
+		}
+
+		for ship in self.ships.iter_mut() {
+			ship.update(dt, &self.physics);
+		}
+		self.tick_all_bullets(dt);
+		self.apply_bounds();
+
+		if let Some(zone) = self.safe_zone.as_mut() {
+			zone.shrink(dt);
+			for ship in self.ships.iter_mut() {
+				if !zone.contains(Vector::from(ship.position)) {
+					ship.take_damage(zone.damage_rate * dt);
+				}
+			}
+		}
+
+		if self.ship_collision {
+			self.resolve_ship_collisions();
+		}
+		self.resolve_obstacle_collisions(&mut removed);
+		self.resolve_collisions(&mut removed);
+
+		self.ships.retain(|ship| {
+			if ship.is_destroyed() {
+				removed.ships.push(ship.id);
+				return false;
+			}
+
+			true
+		});
+
+		for id in removed.ships.iter() {
+			self.inputs.remove(id);
+			self.bots.remove(id);
+			for player in self.players.iter_mut() {
+				player.ships.remove(id);
+			}
+		}
+
+		removed
+	}
+
+	fn tick_all_bullets(&mut self, dt: f32) {
+		for bullet in self.bullets.iter_mut() {
+			bullet.update(dt, &self.physics);
+		}
+	}
+
+	// Separates overlapping ships and bounces them apart elastically, both in proportion to
+	// their inverse mass.
+	fn resolve_ship_collisions(&mut self) {
+		let mut grid = SpatialGrid::new(Self::GRID_CELL_SIZE);
+		let mut max_radius: f32 = 0.0;
+		for (index, ship) in self.ships.iter().enumerate() {
+			grid.insert(index, ship.position.into());
+			max_radius = max_radius.max(ship.radius);
+		}
+
+		for index in 0..self.ships.len() {
+			let query = grid.query(self.ships[index].position.into(), 2.0 * max_radius);
+			for other in query.into_iter().filter(|&other| other > index) {
+				let (a, b) = (&self.ships[index], &self.ships[other]);
+				let delta = b.position - a.position;
+				let distance = delta.length();
+				let reach = (a.radius + b.radius) as f64;
+				// Ships exactly on top of each other have no direction to be pushed in.
+				if distance >= reach || distance == 0.0 {
+					continue;
+				}
+
+				let normal = delta / distance;
+				let (inverse_a, inverse_b) = (1.0 / a.mass as f64, 1.0 / b.mass as f64);
+				let inverse_total = inverse_a + inverse_b;
+
+				let correction = normal * ((reach - distance) / inverse_total);
+				let approach = (b.velocity - a.velocity).dot(normal);
+				let impulse = if approach < 0.0 {
+					normal * (-2.0 * approach / inverse_total)
+				} else {
+					Vector64::default()
+				};
+
+				let a = &mut self.ships[index];
+				a.position -= correction * inverse_a;
+				a.velocity -= impulse * inverse_a;
+				let b = &mut self.ships[other];
+				b.position += correction * inverse_b;
+				b.velocity += impulse * inverse_b;
+			}
+		}
+	}
+
+	// Pushes ships out of the obstacles, keeping only the velocity along the surface, and
+	// removes the bullets that hit one.
+	fn resolve_obstacle_collisions(&mut self, removed: &mut RemovedEntities) {
+		if self.obstacles.is_empty() {
+			return;
+		}
+
+		for ship in self.ships.iter_mut() {
+			for obstacle in self.obstacles.iter() {
+				let Some(push) = obstacle.shape.separation(&ship.collider()) else {
+					continue;
+				};
+				ship.position += Vector64::from(push);
+
+				let normal = Vector64::from(push.normalize_or_zero());
+				let approach = ship.velocity.dot(normal);
+				if approach < 0.0 {
+					ship.velocity -= normal * approach;
+				}
+			}
+		}
+
+		self.bullets.retain(|bullet| {
+			let hit = self
+				.obstacles
+				.iter()
+				.any(|obstacle| obstacle.intersects(&bullet.collider()));
+			if hit {
+				removed.bullets.push(bullet.id);
+			}
+
+			!hit
+		});
+	}
+
+	fn resolve_collisions(&mut self, removed: &mut RemovedEntities) {
+		let mut consumed = vec![false; self.bullets.len()];
+
+		if self.bullet_collision {
+			let mut grid = SpatialGrid::new(Self::GRID_CELL_SIZE);
+			for (index, bullet) in self.bullets.iter().enumerate() {
+				grid.insert(index, bullet.position.into());
+			}
+
+			for (index, bullet) in self.bullets.iter().enumerate() {
+				if consumed[index] {
+					continue;
+				}
+
+				let other = grid
+					.query(bullet.position.into(), 2.0 * Bullet::RADIUS)
+					.into_iter()
+					.find(|&other| {
+						let other_bullet = &self.bullets[other];
+						other != index
+							&& !consumed[other] && other_bullet.owner_id != bullet.owner_id
+							&& other_bullet.collider().intersects(&bullet.collider())
+					});
+
+				if let Some(other) = other {
+					consumed[index] = true;
+					consumed[other] = true;
+				}
+			}
+		}
+
+		let mut grid = SpatialGrid::new(Self::GRID_CELL_SIZE);
+		let mut max_radius: f32 = 0.0;
+		for (index, ship) in self.ships.iter().enumerate() {
+			grid.insert(index, ship.position.into());
+			max_radius = max_radius.max(ship.radius);
+		}
+
+		let pass_through = self.invulnerability_mode == InvulnerabilityMode::PassThrough;
+		for (index, bullet) in self.bullets.iter_mut().enumerate() {
+			if consumed[index] {
+				continue;
+			}
+
+			let target = grid
+				.query(bullet.position.into(), max_radius + Bullet::RADIUS)
+				.into_iter()
+				.find(|&target| {
+					let ship = &self.ships[target];
+					ship.id != bullet.owner_id
+						&& !(pass_through && ship.is_invulnerable(self.tick))
+						&& !bullet.hit_ships.contains(&ship.id)
+						&& ship.collider().intersects(&bullet.collider())
+				});
+
+			if let Some(target) = target {
+				let ship = &mut self.ships[target];
+				if ship.is_invulnerable(self.tick) {
+					// Absorbed without harm.
+					consumed[index] = true;
+					continue;
+				}
+
+				ship.receive_hit(bullet);
+				if bullet.pierces_remaining == 0 {
+					consumed[index] = true;
+				} else {
+					bullet.pierces_remaining -= 1;
+					bullet.hit_ships.push(ship.id);
+				}
+			}
+		}
+
+		let mut consumed = consumed.into_iter();
+		self.bullets.retain(|bullet| {
+			if consumed.next().unwrap_or(false) {
+				removed.bullets.push(bullet.id);
+				return false;
+			}
+
+			true
+		});
+	}
+
+	fn apply_bounds(&mut self) {
+		let (min, max) = self.world_bounds();
+		let (min, max) = (Vector64::from(min), Vector64::from(max));
+
+		for ship in self.ships.iter_mut() {
+			self.bounds_mode
+				.apply(min, max, &mut ship.position, &mut ship.velocity);
+		}
+		for bullet in self.bullets.iter_mut() {
+			self.bounds_mode
+				.apply(min, max, &mut bullet.position, &mut bullet.velocity);
+		}
+	}
+
+	// A server tick is, in this order:
+	// 1. `net_poll` reads what the players sent,
+	// 2. `apply_all_inputs` hands the received inputs to their ships,
+	// 3. `advance`, or `step` directly, integrates movement and resolves collisions,
+	// 4. `broadcast_state` sends the result back.
+	// Inputs applied after `step` only take effect on the next tick.
+
+	// Reads whatever the players sent since the last call. Players whose connection failed are
+	// dropped together with their ships.
+	pub fn net_poll(&mut self) -> TickReport {
+		let mut report = TickReport::default();
+		for (index, player) in self.players.iter_mut().enumerate() {
+			let (packets, skipped) = match player.receive() {
+				Ok(received) => received,
+				Err(_) => {
+					report.disconnected.push(index);
+					continue;
+				}
+			};
+			self.malformed_packets += skipped;
+
+			for packet in packets {
+				report.packets_received += 1;
+
+				// Unknown protocols are still read to keep the stream framed, then dropped.
+				if !is_known_protocol(packet.protocol_id()) {
+					self.malformed_packets += 1;
+					continue;
+				}
+
+				if let (
+					Some(recorder),
+					PacketProtocol::Raw {
+						id,
+						protocol,
+						content,
+					},
+				) = (self.recorder.as_mut(), &packet)
+				{
+					let mut bytes = encode_header(*id, *protocol, content.len() as u32).to_vec();
+					bytes.extend(content);
+					recorder.record(self.tick, index, &bytes);
+				}
+
+				player.inbound.push(packet);
+			}
+		}
+
+		for &index in report.disconnected.iter().rev() {
+			report.removed_ships.extend(self.remove_player(index));
+			self.dropped_connections += 1;
+		}
+
+		report
+	}
+
+	// Drops the player and the ships it controls, returning their ids.
+	pub fn remove_player(&mut self, index: usize) -> Vec<ShipId> {
+		let player = self.players.remove(index);
+		self.ships.retain(|ship| !player.ships.contains(&ship.id));
+		for id in player.ships.iter() {
+			self.inputs.remove(id);
+		}
+
+		player.ships.into_iter().collect()
+	}
+
+	// Server Message:
+	// [ 64 bits |        32 bits          |  32 bits   | ship count * ship |   32 bits    | bullet count * bullet ]
+	// [  tick   | last processed sequence | ship count |       ships       | bullet count |        bullets        ]
+
+	// Ship:
+	// [ 32 bits   |     2 * 2 * 32 bits   |   32 bits   | 8 bits |  8 bits   | 32 bits |    8 bits    ]
+	// [ player id | position and velocity | orientation | design | propulsor |  hits   | invulnerable ]
+
+	// Bullet:
+	// [  32 bits  |     2 * 2 * 32 bits   | 32 bits  ]
+	// [ bullet id | position and velocity | owner id ]
+	fn send_server_binary_message(
+		&self,
+		array: &mut Vec<u8>,
+		last_processed_seq: u32,
+		ships: &[&Ship],
+		bullets: &[&Bullet],
+	) {
+		array.reserve(Self::message_size(ships.len(), bullets.len()));
+
+		array.extend(self.tick.to_be_bytes());
+		array.extend(last_processed_seq.to_be_bytes());
+		array.extend((ships.len() as u32).to_be_bytes());
+		for ship in ships.iter() {
+			// Quantized to the `f32` wire type.
+			let (position, velocity) = (Vector::from(ship.position), Vector::from(ship.velocity));
+			array.extend(ship.id.0.to_be_bytes());
+			array.extend(position.x.to_be_bytes());
+			array.extend(position.y.to_be_bytes());
+			array.extend(velocity.x.to_be_bytes());
+			array.extend(velocity.y.to_be_bytes());
+			array.extend(ship.orientation.to_be_bytes());
+			array.push(ship.design);
+
+			array.push(pack_propulsor(ship.propulsor));
+			array.extend(ship.hits.to_be_bytes());
+			array.push(ship.is_invulnerable(self.tick) as u8);
+		}
+
+		array.extend((bullets.len() as u32).to_be_bytes());
+		for bullet in bullets.iter() {
+			let (position, velocity) =
+				(Vector::from(bullet.position), Vector::from(bullet.velocity));
+			array.extend(bullet.id.0.to_be_bytes());
+			array.extend(position.x.to_be_bytes());
+			array.extend(position.y.to_be_bytes());
+			array.extend(velocity.x.to_be_bytes());
+			array.extend(velocity.y.to_be_bytes());
+			array.extend(bullet.owner_id.0.to_be_bytes());
+		}
+	}
+
+	fn message_size(ships: usize, bullets: usize) -> usize {
+		Self::MESSAGE_PREFIX_SIZE + ships * Self::SHIP_WIRE_SIZE + bullets * Self::BULLET_WIRE_SIZE
+	}
+
+	// Sends the world state to every player, unless the broadcast interval has not elapsed yet.
+	// Whatever a socket does not take now is retried on the next broadcast, returns the indices
+	// of the players whose connection failed.
+	pub fn broadcast_state(&mut self) -> Vec<usize> {
+		if !self.broadcast_due() {
+			return Vec::new();
+		}
+		self.ticks_since_broadcast = 0;
+
+		let packets = self.state_packets();
+
+		let mut failed = Vec::new();
+		for (index, packet) in packets.iter().enumerate() {
+			let player = &mut self.players[index];
+			player.queue(packet, ServerPacket::priority());
+			if player.flush().is_err() {
+				failed.push(index);
+			}
+		}
+
+		failed
+	}
+
+	// One world state packet per player, filtered by the view radius when one is set.
+	fn state_packets(&self) -> Vec<Vec<u8>> {
+		let id = ServerPacket::id() as i32;
+		let Some(radius) = self.view_radius else {
+			return self
+				.players
+				.iter()
+				.map(|player| self.send_server_packet(id, 0, player.last_processed_seq))
+				.collect();
+		};
+
+		let mut ship_grid = SpatialGrid::new(radius);
+		for (index, ship) in self.ships.iter().enumerate() {
+			ship_grid.insert(index, ship.position.into());
+		}
+		let mut bullet_grid = SpatialGrid::new(radius);
+		for (index, bullet) in self.bullets.iter().enumerate() {
+			bullet_grid.insert(index, bullet.position.into());
+		}
+
+		self.players
+			.iter()
+			.map(|player| {
+				let (ships, bullets) = self.visible_to(player, radius, &ship_grid, &bullet_grid);
+				self.state_packet(id, 0, player.last_processed_seq, &ships, &bullets)
+			})
+			.collect()
+	}
+
+	// Ships and bullets within `radius` of any of the player's ships, plus those ships.
+	fn visible_to(
+		&self,
+		player: &PlayerData,
+		radius: f32,
+		ship_grid: &SpatialGrid<usize>,
+		bullet_grid: &SpatialGrid<usize>,
+	) -> (Vec<&Ship>, Vec<&Bullet>) {
+		let radius_squared = (radius * radius) as f64;
+		let mut ships = BTreeSet::new();
+		let mut bullets = BTreeSet::new();
+		for (index, viewer) in self.ships.iter().enumerate() {
+			if !player.ships.contains(&viewer.id) {
+				continue;
+			}
+			ships.insert(index);
+
+			for index in ship_grid.query(viewer.position.into(), radius) {
+				if self.ships[index].position.distance_squared(viewer.position) <= radius_squared {
+					ships.insert(index);
+				}
+			}
+			for index in bullet_grid.query(viewer.position.into(), radius) {
+				if self.bullets[index]
+					.position
+					.distance_squared(viewer.position)
+					<= radius_squared
+				{
+					bullets.insert(index);
+				}
+			}
+		}
+
+		(
+			ships.into_iter().map(|index| &self.ships[index]).collect(),
+			bullets
+				.into_iter()
+				.map(|index| &self.bullets[index])
+				.collect(),
+		)
+	}
+
+	pub fn send_server_packet(&self, id: i32, protocol_id: u8, last_processed_seq: u32) -> Vec<u8> {
+		let ships: Vec<&Ship> = self.ships.iter().collect();
+		let bullets: Vec<&Bullet> = self.bullets.iter().collect();
+		self.state_packet(id, protocol_id, last_processed_seq, &ships, &bullets)
+	}
+
+	fn state_packet(
+		&self,
+		id: i32,
+		protocol_id: u8,
+		last_processed_seq: u32,
+		ships: &[&Ship],
+		bullets: &[&Bullet],
+	) -> Vec<u8> {
+		// Leave room for the header, it is written in place once the message is.
+		let mut packet =
+			Vec::with_capacity(HEADER_LEN + Self::message_size(ships.len(), bullets.len()));
+		packet.resize(HEADER_LEN, 0);
+		match protocol_id {
+			0 => self.send_server_binary_message(&mut packet, last_processed_seq, ships, bullets),
+			_ => packet.extend([0, 0, 0, 0]), // i32 zero.
+		}
+
+		let content_length = (packet.len() - HEADER_LEN) as u32;
+		HeaderFormat::DEFAULT.write(&mut packet, id as u32, protocol_id, content_length);
+		packet
+	}
+}
+
+impl Default for Game {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use std::collections::VecDeque;
+	use std::net::{Shutdown, TcpListener};
+	use std::sync::atomic::{AtomicBool, Ordering};
+	use std::sync::{Arc, Mutex};
+	use std::time::Instant;
+
+	use super::*;
+	use crate::protocol::{decode_header, ClientPacket, Packet, StateMessage, MAX_CONTENT_LENGTH};
+
+	// In-memory player connection. Reads return what was pushed, then `WouldBlock`, or end of
+	// file once closed. Writes are kept. With a write budget, writes take a few bytes at a time
+	// and block once the budget is spent, like a busy socket.
+	#[derive(Clone, Default)]
+	pub(crate) struct MockStream {
+		inbound: Arc<Mutex<VecDeque<u8>>>,
+		outbound: Arc<Mutex<Vec<u8>>>,
+		closed: Arc<AtomicBool>,
+		write_budget: Arc<Mutex<Option<usize>>>,
+	}
+
+	impl MockStream {
+		pub(crate) fn push(&self, bytes: &[u8]) {
+			self.inbound.lock().unwrap().extend(bytes);
+		}
+
+		pub(crate) fn close(&self) {
+			self.closed.store(true, Ordering::SeqCst);
+		}
+
+		pub(crate) fn set_write_budget(&self, budget: Option<usize>) {
+			*self.write_budget.lock().unwrap() = budget;
+		}
+
+		pub(crate) fn take_written(&self) -> Vec<u8> {
+			mem::take(&mut *self.outbound.lock().unwrap())
+		}
+
+		// Every complete packet written so far, as id, protocol and content.
+		pub(crate) fn take_packets(&self) -> Vec<(u32, u8, Vec<u8>)> {
+			let mut buf = PacketBuf::new();
+			let mut packets = Vec::new();
+			let mut packet = buf.process::<ServerPacket>(&self.take_written()).unwrap();
+			while let Some(PacketProtocol::Raw {
+				id,
+				protocol,
+				content,
+			}) = packet
+			{
+				packets.push((id, protocol, content));
+				packet = buf.process(&[]).unwrap();
+			}
+
+			packets
+		}
+	}
+
+	// Decodes a packet returned by `MockStream::take_packets`.
+	pub(crate) fn decode<T: Packet>(packet: &(u32, u8, Vec<u8>)) -> T {
+		let (id, protocol, content) = packet.clone();
+		assert_eq!(id, T::id());
+		PacketProtocol::<T>::Raw {
+			id,
+			protocol,
+			content,
+		}
+		.deserialize()
+		.unwrap()
+	}
+
+	impl Read for MockStream {
+		fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+			let mut inbound = self.inbound.lock().unwrap();
+			if inbound.is_empty() {
+				if self.closed.load(Ordering::SeqCst) {
+					return Ok(0);
+				}
+				return Err(ErrorKind::WouldBlock.into());
+			}
+
+			let size = buf.len().min(inbound.len());
+			for (byte, value) in buf.iter_mut().zip(inbound.drain(..size)) {
+				*byte = value;
+			}
+			Ok(size)
+		}
+	}
+
+	impl Write for MockStream {
+		fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+			let size = match self.write_budget.lock().unwrap().as_mut() {
+				Some(0) => return Err(ErrorKind::WouldBlock.into()),
+				Some(budget) => {
+					let size = buf.len().min(*budget).min(3);
+					*budget -= size;
+					size
+				}
+				None => buf.len(),
+			};
+			self.outbound.lock().unwrap().extend(&buf[..size]);
+			Ok(size)
+		}
+
+		fn flush(&mut self) -> io::Result<()> {
+			Ok(())
+		}
+	}
+
+	// A connected pair of loopback sockets, the game's end first.
+	pub(crate) fn tcp_pair() -> (TcpStream, TcpStream) {
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+		let (server, _) = listener.accept().unwrap();
+		// Like accepted players, so polling never waits for the client.
+		server.set_nonblocking(true).unwrap();
+		(server, client)
+	}
+
+	// The next whole packet the client end receives, header included.
+	fn read_packet_bytes(client: &mut TcpStream) -> Vec<u8> {
+		let mut bytes = vec![0; HEADER_LEN];
+		client.read_exact(&mut bytes).unwrap();
+		let (_, _, length) = decode_header(&bytes).unwrap();
+		bytes.resize(HEADER_LEN + length as usize, 0);
+		client.read_exact(&mut bytes[HEADER_LEN..]).unwrap();
+
+		bytes
+	}
+
+	// Content of a state packet, without the header.
+	pub(crate) fn decode_state(packet: &[u8]) -> StateMessage {
+		StateMessage::decode(&packet[HEADER_LEN..]).unwrap()
+	}
+
+	#[test]
+	fn state_is_quantized_to_f32() {
+		let mut game = Game::new();
+		game.new_player(tcp_pair().0).unwrap();
+		let precise = Vector64 {
+			x: 1.000_000_123_456,
+			y: -2.5,
+		};
+		game.ships[0].position = precise;
+
+		let state = decode_state(&game.send_server_packet(1, 0, 0));
+		assert_eq!(state.ships[0].position, Vector::from(precise));
+		// The simulation itself keeps the full precision.
+		assert_eq!(game.ships()[0].position, precise);
+	}
+
+	#[test]
+	fn accessors_report_players_and_ships() {
+		let mut game = Game::new();
+		assert_eq!((game.players_len(), game.ships_len()), (0, 0));
+
+		game.new_player(tcp_pair().0).unwrap();
+		game.new_player(tcp_pair().0).unwrap();
+		assert_eq!(game.players_len(), 2);
+		assert_eq!(game.ships_len(), 2);
+
+		let ids: Vec<ShipId> = game.ships().iter().map(|ship| ship.id).collect();
+		assert_eq!(ids, vec![ShipId(0), ShipId(1)]);
+		assert!(game.players[1].ships.contains(&ShipId(1)));
+		assert!(game.bullets().is_empty());
+	}
+
+	#[test]
+	fn broadcast_includes_bullets() {
+		let mut game = Game::new();
+		game.new_player(tcp_pair().0).unwrap();
+		let bullet = game.fire(ShipId(0), BulletKind::Standard).unwrap();
+
+		let state = decode_state(&game.send_server_packet(1, 0, 0));
+		assert_eq!(state.ships.len(), 1);
+		assert_eq!(state.bullets.len(), 1);
+		assert_eq!(state.bullets[0].id, bullet.0 as u32);
+		assert_eq!(state.bullets[0].owner_id, 0);
+		assert_eq!(
+			state.bullets[0].position,
+			Vector::from(game.ships()[0].position)
+		);
+	}
+
+	fn bullet_of(kind: BulletKind) -> Bullet {
+		Bullet {
+			velocity: Vector64 {
+				x: kind.speed() as f64,
+				y: 0.0,
+			},
+			kind,
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn heavy_bullets_knock_harder() {
+		let mut game = Game::new();
+		game.new_player(tcp_pair().0).unwrap();
+		game.new_player(tcp_pair().0).unwrap();
+		game.ships[0].receive_hit(&bullet_of(BulletKind::Standard));
+		game.ships[1].receive_hit(&bullet_of(BulletKind::Heavy));
+
+		let (standard, heavy) = (&game.ships[0], &game.ships[1]);
+		assert!(heavy.velocity.x > standard.velocity.x);
+		assert!(heavy.damage > standard.damage);
+		assert_eq!((standard.hits, heavy.hits), (1, 1));
+	}
+
+	#[test]
+	fn fire_spawns_a_bullet_of_the_kind() {
+		let mut game = Game::new();
+		game.new_player(tcp_pair().0).unwrap();
+		let fast = game.fire(ShipId(0), BulletKind::Fast).unwrap();
+		let heavy = game.fire(ShipId(0), BulletKind::Heavy).unwrap();
+		assert_ne!(fast, heavy);
+		assert!(game.fire(ShipId(7), BulletKind::Fast).is_none());
+
+		let bullets = game.bullets();
+		assert_eq!(
+			(bullets[0].kind, bullets[1].kind),
+			(BulletKind::Fast, BulletKind::Heavy)
+		);
+		assert!(bullets.iter().all(|bullet| bullet.owner_id == ShipId(0)));
+		assert!(bullets[0].velocity.x > bullets[1].velocity.x);
+		// Every shot pushes the ship back.
+		assert!(game.ships()[0].velocity.x < 0.0);
+	}
+
+	#[test]
+	fn damage_accumulates_until_destroyed() {
+		let mut game = Game::new();
+		game.new_player(tcp_pair().0).unwrap();
+		let ship = &mut game.ships[0];
+		ship.receive_hit(&bullet_of(BulletKind::Fast));
+		ship.receive_hit(&bullet_of(BulletKind::Fast));
+		ship.receive_hit(&bullet_of(BulletKind::Heavy));
+		assert_eq!(ship.damage, 4.0);
+		assert_eq!(ship.hits, 3);
+
+		ship.receive_hit(&bullet_of(BulletKind::Heavy));
+		assert!(!ship.is_destroyed());
+		ship.receive_hit(&bullet_of(BulletKind::Heavy));
+		assert_eq!(ship.damage, Ship::MAX_HEALTH);
+		assert!(ship.is_destroyed());
+	}
+
+	fn ship_mut(game: &mut Game, id: ShipId) -> &mut Ship {
+		game.ships.iter_mut().find(|ship| ship.id == id).unwrap()
+	}
+
+	#[test]
+	fn step_reports_destroyed_ships() {
+		let mut game = Game::new();
+		game.new_player(tcp_pair().0).unwrap();
+		game.new_player(tcp_pair().0).unwrap();
+		let (doomed, survivor) = (ShipId(0), ShipId(1));
+		ship_mut(&mut game, doomed).take_damage(Ship::MAX_HEALTH);
+
+		let removed = game.step(0.1);
+		assert_eq!(removed.ships, vec![doomed]);
+		assert!(removed.bullets.is_empty());
+		let ids: Vec<ShipId> = game.ships().iter().map(|ship| ship.id).collect();
+		assert_eq!(ids, vec![survivor]);
+		// The owner no longer lists the removed ship.
+		assert!(game.players[0].ships.is_empty());
+		assert!(game.players[1].ships.contains(&survivor));
+		assert!(game.step(0.1).ships.is_empty());
+	}
+
+	#[test]
+	fn clamp_stops_ship_at_the_wall() {
+		let mut game = Game::new();
+		game.set_bounds_mode(BoundsMode::Clamp);
+		game.new_player(tcp_pair().0).unwrap();
+		let (_, max) = game.world_bounds();
+		let ship = ship_mut(&mut game, ShipId(0));
+		ship.position = Vector64 {
+			x: max.x as f64 - 0.1,
+			y: 0.0,
+		};
+		ship.velocity = Vector64 { x: 10.0, y: 1.0 };
+
+		game.step(0.1);
+		let ship = &game.ships()[0];
+		assert_eq!(ship.position.x, max.x as f64);
+		assert_eq!(ship.velocity, Vector64 { x: 0.0, y: 1.0 });
+	}
+
+	#[test]
+	fn ship_and_bullet_round_trip_through_json() {
+		let ship = Ship {
+			id: ShipId(4),
+			position: Vector64 { x: 1.5, y: -2.25 },
+			velocity: Vector64 { x: 0.1, y: 0.2 },
+			orientation: 0.75,
+			design: 2,
+			propulsor: [true, false, false, true],
+			can_shoot: 1,
+			hits: 2,
+			damage: 3.5,
+			max_health: Ship::MAX_HEALTH,
+			radius: Ship::RADIUS,
+			mass: 1.5,
+			team: 1,
+			invulnerable_until_tick: 12,
+			invulnerable: true,
+			acceleration: Vector { x: 0.5, y: 0.0 },
+			heat: 2.5,
+			overheated: false,
+			target_orientation: Some(-1.0),
+		};
+		let json = serde_json::to_string(&ship).unwrap();
+		assert_eq!(serde_json::from_str::<Ship>(&json).unwrap(), ship);
+
+		let bullet = Bullet {
+			owner_id: ShipId(4),
+			..bullet_of(BulletKind::Fast)
+		};
+		let json = serde_json::to_string(&bullet).unwrap();
+		assert_eq!(serde_json::from_str::<Bullet>(&json).unwrap(), bullet);
+	}
+
+	#[test]
+	fn new_player_returns_sequential_ids() {
+		let mut game = Game::new();
+		let ids: Vec<ShipId> = (0..3)
+			.map(|_| game.new_player(tcp_pair().0).unwrap().unwrap())
+			.collect();
+		assert_eq!(ids, vec![ShipId(0), ShipId(1), ShipId(2)]);
+		for (player, id) in game.players.iter().zip(&ids) {
+			assert!(player.ships.contains(id));
+		}
+
+		// Ids are not reused once a ship is removed.
+		ship_mut(&mut game, ShipId(2)).take_damage(Ship::MAX_HEALTH);
+		game.step(0.1);
+		assert_eq!(game.new_player(tcp_pair().0).unwrap().unwrap(), ShipId(3));
+	}
+
+	#[test]
+	fn welcome_is_sent_on_join() {
+		let mut game = Game::new();
+		game.new_player(tcp_pair().0).unwrap();
+		let (stream, mut client) = tcp_pair();
+		let id = game.new_player(stream).unwrap().unwrap();
+
+		let bytes = read_packet_bytes(&mut client);
+		assert_eq!(decode_header(&bytes).unwrap().0, WelcomePacket::id());
+		let welcome: WelcomePacket = PacketProtocol::try_from(bytes.as_slice())
+			.unwrap()
+			.deserialize()
+			.unwrap();
+		assert_eq!(welcome.your_ship_id, 1);
+		assert_eq!(welcome.your_ship_id, id.0 as u32);
+		assert_eq!(
+			(welcome.world_width, welcome.world_height),
+			(Game::DEFAULT_WORLD_WIDTH, Game::DEFAULT_WORLD_HEIGHT)
+		);
+		assert_eq!(welcome.tick_rate, game.tick_rate);
+		assert_eq!(
+			(welcome.thrust, welcome.recoil),
+			(Ship::THRUST, Ship::RECOIL)
+		);
+	}
+
+	fn input(ship_id: ShipId, client_tick: u32, orientation: f32) -> ClientData {
+		ClientData {
+			ship_id,
+			orientation,
+			propulsor: [false; 4],
+			client_tick,
+			sequence: 0,
+		}
+	}
+
+	#[test]
+	fn buffered_inputs_apply_one_per_step_in_order() {
+		let mut game = Game::new();
+		let id = game.new_player(tcp_pair().0).unwrap().unwrap();
+		for client_tick in [3, 1, 2] {
+			game.queue_input(input(id, client_tick, client_tick as f32 / 10.0));
+		}
+
+		for client_tick in 1..=3 {
+			game.apply_all_inputs();
+			game.step(0.1);
+			// Wrapping the orientation into [-PI, PI) may cost the last bit.
+			assert!((game.ships()[0].orientation - client_tick as f32 / 10.0).abs() < 1e-5);
+		}
+
+		// Older than the last applied one.
+		game.queue_input(input(id, 2, 1.0));
+		game.apply_all_inputs();
+		game.step(0.1);
+		assert!((game.ships()[0].orientation - 0.3).abs() < 1e-5);
+	}
+
+	#[test]
+	fn input_backlog_is_bounded() {
+		let mut game = Game::new();
+		let id = game.new_player(tcp_pair().0).unwrap().unwrap();
+		for client_tick in 0..20 {
+			game.queue_input(input(id, client_tick, 0.0));
+		}
+		assert_eq!(game.inputs[&id].pending.len(), InputBuffer::MAX_PENDING);
+
+		// The oldest inputs were dropped.
+		assert_eq!(
+			game.inputs.get_mut(&id).unwrap().pop().unwrap().client_tick,
+			12
+		);
+	}
+
+	#[test]
+	fn shrinking_the_world_pulls_ships_in() {
+		let mut game = Game::new();
+		game.set_bounds_mode(BoundsMode::Clamp);
+		let id = game.spawn_ship();
+		ship_mut(&mut game, id).position = Vector64 { x: 80.0, y: -90.0 };
+
+		game.set_world_size(100.0, 50.0).unwrap();
+		assert_eq!(game.ships()[0].position, Vector64 { x: 50.0, y: -25.0 });
+	}
+
+	#[test]
+	fn rejects_invalid_world_sizes() {
+		let mut game = Game::new();
+		for mode in [BoundsMode::Clamp, BoundsMode::Wrap] {
+			game.set_bounds_mode(mode);
+			for (width, height) in [
+				(-1.0, 10.0),
+				(10.0, 0.0),
+				(f32::NAN, 10.0),
+				(10.0, f32::INFINITY),
+			] {
+				assert!(game.set_world_size(width, height).is_err());
+			}
+		}
+		assert_eq!(
+			game.world_bounds().1,
+			Vector {
+				x: Game::DEFAULT_WORLD_WIDTH / 2.0,
+				y: Game::DEFAULT_WORLD_HEIGHT / 2.0
+			}
+		);
+	}
+
+	#[test]
+	fn shrinking_zone_damages_ships_left_outside() {
+		let mut game = Game::new();
+		let id = game.spawn_ship();
+		ship_mut(&mut game, id).position = Vector64 { x: 8.0, y: 0.0 };
+		game.set_safe_zone(Some(SafeZone {
+			center: Vector { x: 0.0, y: 0.0 },
+			radius: 10.0,
+			min_radius: 2.0,
+			shrink_rate: 1.0,
+			damage_rate: 2.0,
+		}));
+
+		// Still inside until the radius drops below 8.
+		for _ in 0..19 {
+			game.step(0.1);
+		}
+		assert_eq!(game.ships()[0].damage, 0.0);
+
+		for _ in 0..10 {
+			game.step(0.1);
+		}
+		let damage = game.ships()[0].damage;
+		// Nine or ten steps outside, depending on rounding at the edge.
+		assert!(damage > 1.7 && damage < 2.1, "{damage}");
+		assert!((game.safe_zone().unwrap().radius - 7.1).abs() < 1e-3);
+	}
+
+	#[test]
+	fn unknown_protocols_are_counted_and_skipped() {
+		let mut game = Game::new();
+		game.set_recorder(Some(ReplayRecorder::new()));
+		let (stream, mut client) = tcp_pair();
+		game.new_player(stream).unwrap();
+
+		let packet = PacketProtocol::Zero(ClientPacket {
+			player_id: 0,
+			orientation: 0.0,
+			propulsor: 0b0001,
+			client_tick: 1,
+			sequence: 0,
+		})
+		.serialize()
+		.unwrap();
+		client
+			.write_all(&encode_header(ClientPacket::id(), 9, 4))
+			.unwrap();
+		client.write_all(&[1, 2, 3, 4]).unwrap();
+		client.write_all(&packet).unwrap();
+		game.net_poll();
+		game.net_poll();
+
+		assert_eq!(game.malformed_packets(), 1);
+		// The stream stays framed, the next packet is read whole.
+		let entries = game.take_recorder().unwrap().entries().to_vec();
+		assert_eq!(entries.len(), 1);
+		assert_eq!(entries[0].bytes, packet);
+	}
+
+	#[test]
+	fn failed_welcomes_count_as_dropped_connections() {
+		let mut game = Game::new();
+		let (stream, _client) = tcp_pair();
+		stream.shutdown(Shutdown::Write).unwrap();
+
+		assert!(game.new_player(stream).is_err());
+		assert_eq!(game.dropped_connections(), 1);
+		assert_eq!((game.players_len(), game.ships_len()), (0, 0));
+	}
+
+	#[test]
+	fn opposing_bullets_cancel_only_when_enabled() {
+		for enabled in [false, true] {
+			let mut game = Game::new();
+			game.set_bullet_collision(enabled);
+			let (a, b) = (game.spawn_ship(), game.spawn_ship());
+			for id in [a, a, b] {
+				game.fire(id, BulletKind::Standard);
+			}
+			// All three on top of each other, away from the ships.
+			for bullet in game.bullets.iter_mut() {
+				bullet.position = Vector64 { x: 50.0, y: 50.0 };
+				bullet.velocity = Vector64::default();
+			}
+
+			// Only one of `a`'s bullets can cancel `b`'s, bullets of the same owner never do.
+			let removed = game.step(0.1);
+			let expected = if enabled { 2 } else { 0 };
+			assert_eq!(removed.bullets.len(), expected);
+			assert_eq!(game.bullets().len(), 3 - expected);
+		}
+	}
+
+	#[test]
+	fn bounding_circle_follows_the_ship() {
+		let mut game = Game::new();
+		let id = game.spawn_ship();
+		assert_eq!(game.ships()[0].bounding_circle().1, Ship::RADIUS);
+
+		let ship = ship_mut(&mut game, id);
+		ship.position = Vector64 { x: 3.0, y: -1.0 };
+		ship.radius = 2.5;
+		assert_eq!(ship.bounding_circle(), (Vector { x: 3.0, y: -1.0 }, 2.5));
+	}
+
+	// Takes at most three bytes per write and blocks once its budget is spent, like a busy socket.
+	struct ThrottledWriter {
+		written: Vec<u8>,
+		budget: usize,
+		interrupted: bool,
+	}
+
+	impl Write for ThrottledWriter {
+		fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+			if self.interrupted {
+				self.interrupted = false;
+				return Err(ErrorKind::Interrupted.into());
+			}
+			if self.budget == 0 {
+				return Err(ErrorKind::WouldBlock.into());
+			}
+
+			let size = buf.len().min(self.budget).min(3);
+			self.budget -= size;
+			self.written.extend(&buf[..size]);
+			Ok(size)
+		}
+
+		fn flush(&mut self) -> io::Result<()> {
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn partial_writes_arrive_intact_and_in_order() {
+		let mut game = Game::new();
+		game.spawn_ship();
+		let mut writer = ThrottledWriter {
+			written: Vec::new(),
+			budget: 0,
+			interrupted: true,
+		};
+
+		let mut pending = Vec::new();
+		let mut sent = Vec::new();
+		for _ in 0..10 {
+			let packet = game.send_server_packet(ServerPacket::id() as i32, 0, 0);
+			pending.extend(&packet);
+			sent.extend(packet);
+			writer.budget = 20;
+			write_pending(&mut writer, &mut pending).unwrap();
+			game.step(0.1);
+		}
+		// Far less than was queued got through, the rest waits in order.
+		assert_eq!(writer.written.len(), 10 * 20);
+		assert_eq!([writer.written.as_slice(), &pending].concat(), sent);
+
+		writer.budget = usize::MAX;
+		write_pending(&mut writer, &mut pending).unwrap();
+		assert!(pending.is_empty());
+		assert_eq!(writer.written, sent);
+	}
+
+	#[test]
+	fn broadcast_reaches_every_player() {
+		let mut game = Game::new();
+		let mut clients = Vec::new();
+		for _ in 0..2 {
+			let (stream, client) = tcp_pair();
+			game.new_player(stream).unwrap();
+			clients.push(client);
+		}
+		assert!(game.broadcast_state().is_empty());
+
+		let state = game.send_server_packet(ServerPacket::id() as i32, 0, 0);
+		for client in clients.iter_mut() {
+			let welcome = read_packet_bytes(client);
+			assert_eq!(decode_header(&welcome).unwrap().0, WelcomePacket::id());
+			assert_eq!(read_packet_bytes(client), state);
+		}
+	}
+
+	#[test]
+	fn state_carries_the_float_orientation() {
+		let mut game = Game::new();
+		game.spawn_ship();
+		game.ships[0].orientation = -1.25;
+
+		let state = decode_state(&game.send_server_packet(ServerPacket::id() as i32, 0, 0));
+		assert_eq!(state.ships[0].orientation, -1.25);
+	}
+
+	fn send_input(client: &mut TcpStream, input: ClientData) {
+		let packet = PacketProtocol::Zero(ClientPacket::from(&input));
+		client.write_all(&packet.serialize().unwrap()).unwrap();
+	}
+
+	#[test]
+	fn apply_all_inputs_updates_every_player() {
+		let mut game = Game::new();
+		let mut clients = Vec::new();
+		let mut ids = Vec::new();
+		for _ in 0..2 {
+			let (stream, client) = tcp_pair();
+			ids.push(game.new_player(stream).unwrap().unwrap());
+			clients.push(client);
+		}
+		for (index, client) in clients.iter_mut().enumerate() {
+			send_input(client, input(ids[index], 1, index as f32 + 1.0));
+		}
+
+		game.net_poll();
+		assert_eq!(game.apply_all_inputs(), 2);
+		for (index, id) in ids.into_iter().enumerate() {
+			let orientation = ship_mut(&mut game, id).orientation;
+			assert!((orientation - (index as f32 + 1.0)).abs() < 1e-5);
+		}
+	}
+
+	#[test]
+	fn foreign_and_malformed_inputs_are_dropped() {
+		let mut game = Game::new();
+		let (stream, mut client) = tcp_pair();
+		game.new_player(stream).unwrap();
+		let other = game.spawn_ship();
+
+		send_input(&mut client, input(other, 1, 2.0));
+		client
+			.write_all(&encode_header(ClientPacket::id(), 0, 3))
+			.unwrap();
+		client.write_all(&[1, 2, 3]).unwrap();
+		game.net_poll();
+		game.net_poll();
+
+		assert_eq!(game.apply_all_inputs(), 0);
+		// Input for a ship the player does not control counts as well.
+		assert_eq!(game.malformed_packets(), 2);
+		assert_eq!(ship_mut(&mut game, other).orientation, 0.0);
+	}
+
+	#[test]
+	fn smoothing_damps_toggled_input() {
+		// Largest change of acceleration between updates while the forward key flips each tick.
+		let max_jump = |input_smoothing: Option<f32>| {
+			let physics = PhysicsConfig {
+				input_smoothing,
+				..Default::default()
+			};
+			let mut ship = Ship::default();
+			let mut jump: f32 = 0.0;
+			for tick in 0..20 {
+				ship.propulsor[0] = tick % 2 == 0;
+				let previous = ship.acceleration;
+				ship.update(0.1, &physics);
+				jump = jump.max((ship.acceleration - previous).length());
+			}
+			jump
+		};
+
+		assert_eq!(max_jump(None), Ship::THRUST);
+		assert!(max_jump(Some(0.2)) < 0.5 * Ship::THRUST);
+	}
+
+	#[test]
+	fn tick_advances_and_is_broadcast() {
+		let mut game = Game::new();
+		game.spawn_ship();
+		for tick in 1..=3 {
+			game.step(0.1);
+			assert_eq!(game.tick(), tick);
+			assert_eq!(decode_state(&game.send_server_packet(1, 0, 0)).tick, tick);
+		}
+
+		// Empty steps count as well.
+		let mut empty = Game::new();
+		empty.step(0.1);
+		assert_eq!(empty.tick(), 1);
+	}
+
+	#[test]
+	fn state_echoes_last_applied_sequence() {
+		let mut game = Game::new();
+		let (stream, mut client) = tcp_pair();
+		let id = game.new_player(stream).unwrap().unwrap();
+		read_packet_bytes(&mut client);
+
+		for (client_tick, sequence) in [(1, 40), (2, 41)] {
+			send_input(
+				&mut client,
+				ClientData {
+					sequence,
+					..input(id, client_tick, 0.5)
+				},
+			);
+		}
+
+		for sequence in [40, 41] {
+			game.net_poll();
+			game.apply_all_inputs();
+			game.step(0.1);
+			game.broadcast_state();
+			let state = decode_state(&read_packet_bytes(&mut client));
+			assert_eq!(state.last_processed_seq, sequence);
+		}
+	}
+
+	// State packet as encoded before the header was written in place, one `extend` at a time.
+	fn reference_state_packet(game: &Game, last_processed_seq: u32) -> Vec<u8> {
+		let mut message = Vec::new();
+		message.extend(game.tick.to_be_bytes());
+		message.extend(last_processed_seq.to_be_bytes());
+		message.extend((game.ships.len() as u32).to_be_bytes());
+		for ship in game.ships.iter() {
+			let (position, velocity) = (Vector::from(ship.position), Vector::from(ship.velocity));
+			message.extend(ship.id.0.to_be_bytes());
+			message.extend(position.x.to_be_bytes());
+			message.extend(position.y.to_be_bytes());
+			message.extend(velocity.x.to_be_bytes());
+			message.extend(velocity.y.to_be_bytes());
+			message.extend(ship.orientation.to_be_bytes());
+			message.push(ship.design);
+			message.push(pack_propulsor(ship.propulsor));
+			message.extend(ship.hits.to_be_bytes());
+			message.push(ship.is_invulnerable(game.tick) as u8);
+		}
+		message.extend((game.bullets.len() as u32).to_be_bytes());
+		for bullet in game.bullets.iter() {
+			let (position, velocity) =
+				(Vector::from(bullet.position), Vector::from(bullet.velocity));
+			message.extend(bullet.id.0.to_be_bytes());
+			message.extend(position.x.to_be_bytes());
+			message.extend(position.y.to_be_bytes());
+			message.extend(velocity.x.to_be_bytes());
+			message.extend(velocity.y.to_be_bytes());
+			message.extend(bullet.owner_id.0.to_be_bytes());
+		}
+
+		let mut packet = Vec::new();
+		packet.extend(encode_header(ServerPacket::id(), 0, message.len() as u32));
+		packet.extend(message);
+		packet
+	}
+
+	fn busy_game(ships: usize) -> Game {
+		let mut game = Game::new();
+		for index in 0..ships {
+			let id = game.spawn_ship();
+			let ship = ship_mut(&mut game, id);
+			ship.orientation = index as f32 * 0.37;
+			ship.propulsor = unpack_propulsor(index as u8);
+			ship.hits = index as i32;
+			game.fire(id, BulletKind::Fast);
+		}
+		game.step(0.1);
+		game
+	}
+
+	#[test]
+	fn state_packet_matches_reference_encoder() {
+		let game = busy_game(5);
+		let packet = game.send_server_packet(ServerPacket::id() as i32, 0, 12);
+		assert_eq!(packet, reference_state_packet(&game, 12));
+		assert_eq!(packet.len(), packet.capacity());
+	}
+
+	// Timing only, run with `cargo test --release -- --ignored --nocapture`.
+	#[test]
+	#[ignore]
+	fn bench_state_packet() {
+		let game = busy_game(200);
+		let iterations = 2_000;
+
+		let started = Instant::now();
+		for _ in 0..iterations {
+			std::hint::black_box(game.send_server_packet(ServerPacket::id() as i32, 0, 0));
+		}
+		let encoder = started.elapsed() / iterations;
+
+		let started = Instant::now();
+		for _ in 0..iterations {
+			std::hint::black_box(reference_state_packet(&game, 0));
+		}
+		let reference = started.elapsed() / iterations;
+
+		println!("state packet: {encoder:?}, reference: {reference:?}");
+	}
+
+	#[test]
+	fn defaults_are_sane() {
+		let ship = Ship::default();
+		assert_eq!(ship.velocity, Vector64::default());
+		assert_eq!((ship.hits, ship.damage), (0, 0.0));
+		assert_eq!(ship.max_health, Ship::MAX_HEALTH);
+		assert!(!ship.is_destroyed());
+		assert_eq!(ship.radius, Ship::RADIUS);
+
+		let bullet = Bullet::default();
+		assert_eq!(bullet.velocity, Vector64::default());
+		assert_eq!(bullet.kind, BulletKind::Standard);
+	}
+
+	#[test]
+	fn bullets_travel_by_velocity() {
+		let mut game = Game::new();
+		let id = game.spawn_ship();
+		let bullet = game.fire(id, BulletKind::Standard).unwrap();
+		let find = |game: &Game| {
+			game.bullets()
+				.iter()
+				.find(|b| b.id == bullet)
+				.unwrap()
+				.clone()
+		};
+		let start = find(&game);
+
+		game.step(0.125);
+		assert_eq!(
+			find(&game).position,
+			start.position + start.velocity * 0.125
+		);
+	}
+
+	#[test]
+	fn with_config_rejects_invalid_tick_rates() {
+		for tick_rate in [0.0, -60.0, f32::NAN, f32::INFINITY] {
+			assert!(Game::with_config(GameConfig {
+				tick_rate,
+				..Default::default()
+			})
+			.is_err());
+		}
+		let game = Game::with_config(GameConfig {
+			tick_rate: 30.0,
+			..Default::default()
+		})
+		.unwrap();
+		assert_eq!(game.welcome_packet(ShipId(0)).tick_rate, 30.0);
+	}
+
+	// Fails with each of `errors` in turn, then reads `data`.
+	struct FlakyReader {
+		errors: Vec<ErrorKind>,
+		data: Vec<u8>,
+	}
+
+	impl Read for FlakyReader {
+		fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+			if !self.errors.is_empty() {
+				return Err(self.errors.remove(0).into());
+			}
+
+			let size = buf.len().min(self.data.len());
+			buf[..size].copy_from_slice(&self.data[..size]);
+			self.data.drain(..size);
+			Ok(size)
+		}
+	}
+
+	#[test]
+	fn read_available_retries_interrupts() {
+		let mut reader = FlakyReader {
+			errors: vec![ErrorKind::Interrupted, ErrorKind::Interrupted],
+			data: vec![1, 2, 3],
+		};
+		let mut buffer = [0; 8];
+		assert_eq!(read_available(&mut reader, &mut buffer).unwrap(), 3);
+		assert_eq!(&buffer[..3], &[1, 2, 3]);
+	}
+
+	#[test]
+	fn read_available_reports_closed_connections() {
+		let mut buffer = [0; 8];
+		let mut blocked = FlakyReader {
+			errors: vec![ErrorKind::WouldBlock],
+			data: Vec::new(),
+		};
+		assert_eq!(read_available(&mut blocked, &mut buffer).unwrap(), 0);
+
+		let error = read_available(&mut blocked, &mut buffer).unwrap_err();
+		assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+		// Nothing was asked for, nothing is wrong.
+		assert_eq!(read_available(&mut blocked, &mut []).unwrap(), 0);
+
+		let mut broken = FlakyReader {
+			errors: vec![ErrorKind::ConnectionReset],
+			data: Vec::new(),
+		};
+		let error = read_available(&mut broken, &mut buffer).unwrap_err();
+		assert_eq!(error.kind(), ErrorKind::ConnectionReset);
+	}
+
+	#[test]
+	fn closed_connections_are_reported() {
+		let mut game = Game::new();
+		let (open, mut open_client) = tcp_pair();
+		let (closed, closed_client) = tcp_pair();
+		let id = game.new_player(open).unwrap().unwrap();
+		game.new_player(closed).unwrap();
+
+		send_input(&mut open_client, input(id, 1, 0.5));
+		drop(closed_client);
+		assert_eq!(game.net_poll().disconnected, vec![1]);
+		assert_eq!(game.dropped_connections(), 1);
+		assert_eq!(game.apply_all_inputs(), 1);
+	}
+
+	#[test]
+	fn spread_fans_out_around_the_heading() {
+		let mut game = Game::new();
+		let id = game.spawn_ship();
+		ship_mut(&mut game, id).orientation = 1.0;
+		let spread = 10f32.to_radians();
+		let ids = game.fire_weapon(id, &Weapon::spread(BulletKind::Standard, 3, spread));
+		assert_eq!(ids.len(), 3);
+
+		let ship_velocity = ship_mut(&mut game, id).velocity;
+		for (bullet, offset) in ids.iter().zip([-spread, 0.0, spread]) {
+			let bullet = game.bullets().iter().find(|b| b.id == *bullet).unwrap();
+			let angle = Vector::from(bullet.velocity - ship_velocity).angle();
+			assert!((angle - (1.0 + offset)).abs() < 1e-4, "{angle} {offset}");
+		}
+	}
+
+	#[test]
+	fn spread_respects_the_bullet_cap() {
+		let mut game = Game::new();
+		game.set_max_bullets_per_ship(Some(4));
+		let id = game.spawn_ship();
+		let weapon = Weapon::spread(BulletKind::Standard, 3, 0.1);
+		assert_eq!(game.fire_weapon(id, &weapon).len(), 3);
+		// Only one more fits, the whole volley is refused.
+		assert!(game.fire_weapon(id, &weapon).is_empty());
+		assert_eq!(game.bullets().len(), 3);
+	}
+
+	#[test]
+	fn nearest_ship_skips_the_excluded_one() {
+		let mut game = Game::new();
+		let ids: Vec<ShipId> = [1.0, 3.0, -7.0]
+			.into_iter()
+			.map(|x| {
+				let id = game.spawn_ship();
+				ship_mut(&mut game, id).position = Vector64 { x, y: 0.0 };
+				id
+			})
+			.collect();
+
+		let origin = Vector { x: 0.0, y: 0.0 };
+		assert_eq!(game.nearest_ship(origin, None).unwrap().id, ids[0]);
+		assert_eq!(game.nearest_ship(origin, Some(ids[0])).unwrap().id, ids[1]);
+		let far_left = Vector { x: -20.0, y: 0.0 };
+		assert_eq!(game.nearest_ship(far_left, None).unwrap().id, ids[2]);
+		assert!(Game::new().nearest_ship(origin, None).is_none());
+	}
+
+	#[test]
+	fn bots_turn_toward_their_target() {
+		let mut game = Game::new();
+		let bot = game.add_bot();
+		let target = game.spawn_ship();
+		ship_mut(&mut game, target).position = Vector64 { x: 0.0, y: 10.0 };
+
+		let mut previous = 0.0;
+		for _ in 0..5 {
+			game.step(0.1);
+			let orientation = ship_mut(&mut game, bot).orientation;
+			assert!(orientation > previous && orientation <= core::f32::consts::FRAC_PI_2);
+			previous = orientation;
+		}
+		assert!(game.is_bot(bot) && !game.is_bot(target));
+
+		game.set_bot_ai(false);
+		game.step(0.1);
+		assert_eq!(ship_mut(&mut game, bot).orientation, previous);
+	}
+
+	#[test]
+	fn view_radius_hides_distant_ships() {
+		let mut game = Game::new();
+		let mut clients = Vec::new();
+		let mut ids = Vec::new();
+		for _ in 0..2 {
+			let (stream, mut client) = tcp_pair();
+			ids.push(game.new_player(stream).unwrap().unwrap());
+			read_packet_bytes(&mut client);
+			clients.push(client);
+		}
+		ship_mut(&mut game, ids[1]).position = Vector64 { x: 50.0, y: 0.0 };
+		let near = game.spawn_ship();
+		ship_mut(&mut game, near).position = Vector64 { x: 3.0, y: 0.0 };
+
+		game.set_view_radius(Some(10.0));
+		game.broadcast_state();
+		let visible = |client: &mut TcpStream| {
+			let state = decode_state(&read_packet_bytes(client));
+			state
+				.ships
+				.iter()
+				.map(|ship| ShipId(ship.player_id as i32))
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(visible(&mut clients[0]), vec![ids[0], near]);
+		assert_eq!(visible(&mut clients[1]), vec![ids[1]]);
+
+		game.set_view_radius(None);
+		game.broadcast_state();
+		assert_eq!(visible(&mut clients[1]).len(), 3);
+	}
+
+	#[test]
+	fn ship_converts_to_server_packet() {
+		let ship = Ship {
+			id: ShipId(6),
+			position: Vector64 { x: 1.0, y: 2.0 },
+			velocity: Vector64 { x: -3.0, y: 4.5 },
+			orientation: 0.25,
+			design: 3,
+			propulsor: [true, true, false, true],
+			hits: 9,
+			invulnerable_until_tick: 4,
+			..Default::default()
+		};
+		let packet = ServerPacket::from(&ship);
+		assert_eq!(
+			serde_json::to_value(&packet).unwrap(),
+			serde_json::json!({
+				"player_id": 6,
+				"position": { "x": 1.0, "y": 2.0 },
+				"velocity": { "x": -3.0, "y": 4.5 },
+				"orientation": 0.25,
+				"design": 3,
+				"propulsor": 0b1011,
+				"hits": 9,
+				"invulnerable": false,
+			})
+		);
+		// The flag follows the game's tick once a step refreshed it.
+		let mut game = Game::new();
+		assert!(game.ship_packet(&ship).invulnerable);
+		game.ships.push(ship);
+		game.step(0.1);
+		assert!(ServerPacket::from(&game.ships[0]).invulnerable);
+		for _ in 0..3 {
+			game.step(0.1);
+		}
+		assert!(!ServerPacket::from(&game.ships[0]).invulnerable);
+		assert!(!game.ship_packet(&game.ships[0]).invulnerable);
+
+		let mut game = Game::new();
+		let id = game.spawn_ship();
+		game.fire(id, BulletKind::Heavy);
+		game.step(0.1);
+		let snapshot = game.snapshot();
+		assert_eq!(snapshot.tick, 1);
+		assert_eq!(snapshot.ships[0].player_id, id.0 as u32);
+		assert_eq!(snapshot.bullets[0].owner_id, id.0);
+	}
+
+	#[test]
+	fn client_data_round_trips_through_client_packet() {
+		let data = ClientData {
+			ship_id: ShipId(12),
+			orientation: -0.5,
+			propulsor: [false, true, true, false],
+			client_tick: 77,
+			sequence: 78,
+		};
+		let packet = ClientPacket::from(&data);
+		assert_eq!((packet.player_id, packet.propulsor), (12, 0b0110));
+		let back = ClientData::from(&packet);
+		assert_eq!((back.ship_id, back.orientation), (ShipId(12), -0.5));
+		assert_eq!(back.propulsor, data.propulsor);
+		assert_eq!((back.client_tick, back.sequence), (77, 78));
+	}
+
+	#[test]
+	fn recoil_pushes_backward() {
+		let mut game = Game::new();
+		game.set_physics(PhysicsConfig {
+			recoil: 0.5,
+			..Default::default()
+		});
+		let id = game.spawn_ship();
+		ship_mut(&mut game, id).orientation = 0.0;
+		game.fire(id, BulletKind::Standard);
+		assert_eq!(
+			ship_mut(&mut game, id).velocity,
+			Vector64 { x: -0.5, y: 0.0 }
+		);
+	}
+
+	#[test]
+	fn oversized_headers_are_counted_as_malformed() {
+		let mut game = Game::new();
+		let (stream, mut client) = tcp_pair();
+		game.new_player(stream).unwrap();
+		read_packet_bytes(&mut client);
+
+		let header = encode_header(ClientPacket::id(), 0, MAX_CONTENT_LENGTH + 1);
+		client.write_all(&header).unwrap();
+		// The header is skipped, the player stays.
+		assert!(game.net_poll().disconnected.is_empty());
+		assert_eq!(game.malformed_packets(), 1);
+	}
+
+	#[test]
+	fn reset_round_respawns_ships_and_drops_inputs() {
+		let mut game = Game::new();
+		let (stream, mut client) = tcp_pair();
+		let id = game.new_player(stream).unwrap().unwrap();
+		let ship = ship_mut(&mut game, id);
+		ship.position = Vector64 { x: 40.0, y: -25.0 };
+		ship.velocity = Vector64 { x: 3.0, y: 1.0 };
+		ship.damage = 5.0;
+		ship.hits = 2;
+		game.queue_input(input(id, 1, 1.0));
+		send_input(&mut client, input(id, 2, 2.0));
+		game.net_poll();
+
+		game.reset_round(false);
+
+		let spawn = game.spawn_point().into();
+		let ship = ship_mut(&mut game, id);
+		assert_eq!(ship.position, spawn);
+		assert_eq!(ship.velocity, Vector64::default());
+		assert_eq!((ship.damage, ship.hits), (0.0, 2));
+		assert_eq!(game.apply_all_inputs(), 0);
+
+		game.reset_round(true);
+		assert_eq!(ship_mut(&mut game, id).hits, 0);
+	}
+
+	#[test]
+	fn empty_step_only_advances_the_tick() {
+		let mut game = Game::new();
+		game.set_safe_zone(Some(SafeZone {
+			center: Vector { x: 0.0, y: 0.0 },
+			radius: 10.0,
+			min_radius: 2.0,
+			shrink_rate: 1.0,
+			damage_rate: 2.0,
+		}));
+		assert!(game.is_idle());
+
+		for _ in 0..10 {
+			assert_eq!(game.step(0.1), RemovedEntities::default());
+		}
+		assert_eq!(game.tick(), 10);
+		assert_eq!(game.safe_zone().unwrap().radius, 10.0);
+	}
+
+	#[test]
+	fn client_data_copies_compare_equal() {
+		let original = input(ShipId(3), 5, 0.75);
+		let mut copy = original;
+		assert_eq!(copy, original);
+
+		copy.propulsor[2] = true;
+		assert_ne!(copy, original);
+		assert_ne!(input(ShipId(3), 6, 0.75), original);
+	}
+
+	#[test]
+	fn tick_report_counts_packets_and_disconnects() {
+		let mut game = Game::new();
+		let mut clients = Vec::new();
+		let mut ids = Vec::new();
+		for _ in 0..3 {
+			let (stream, client) = tcp_pair();
+			ids.push(game.new_player(stream).unwrap().unwrap());
+			clients.push(client);
+		}
+		send_input(&mut clients[0], input(ids[0], 1, 0.5));
+		send_input(&mut clients[2], input(ids[2], 1, 0.5));
+		clients[1].shutdown(Shutdown::Write).unwrap();
+
+		let report = game.net_poll();
+		assert_eq!(report.disconnected, vec![1]);
+		assert_eq!(report.removed_ships, vec![ids[1]]);
+		assert_eq!(report.packets_received, 2);
+		assert_eq!(game.players_len(), 2);
+		assert!(game.ships().iter().all(|ship| ship.id != ids[1]));
+		assert_eq!(game.dropped_connections(), 1);
+
+		send_input(&mut clients[0], input(ids[0], 2, 0.5));
+		send_input(&mut clients[2], input(ids[2], 2, 0.5));
+		let report = game.net_poll();
+		assert_eq!(report.disconnected, Vec::<usize>::new());
+		assert_eq!(report.packets_received, 2);
+	}
+
+	#[test]
+	fn despawn_bullet_removes_only_that_bullet() {
+		let mut game = Game::new();
+		let (first_ship, second_ship) = (game.spawn_ship(), game.spawn_ship());
+		let first = game.fire(first_ship, BulletKind::Standard).unwrap();
+		let second = game.fire(second_ship, BulletKind::Standard).unwrap();
+
+		assert!(game.despawn_bullet(first));
+		assert!(!game.despawn_bullet(first));
+		assert!(game.bullet(first).is_none());
+		assert_eq!(game.bullets().len(), 1);
+		assert_eq!(game.bullets()[0].id, second);
+		assert_eq!(game.bullet(second).unwrap().owner_id, second_ship);
+	}
+
+	#[test]
+	fn channel_streams_join_on_accept_pending() {
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let address = listener.local_addr().unwrap();
+		let mut game = Game::new();
+		let connections = game.connection_channel(2);
+		assert!(game.accept_pending().is_empty());
+
+		let _clients: Vec<_> = (0..3)
+			.map(|_| TcpStream::connect(address).unwrap())
+			.collect();
+		for _ in 0..2 {
+			connections.try_send(listener.accept().unwrap().0).unwrap();
+		}
+		// Bounded, the accept thread has to wait for the game loop.
+		let (stream, _) = listener.accept().unwrap();
+		let Err(mpsc::TrySendError::Full(stream)) = connections.try_send(stream) else {
+			panic!("the channel should be full");
+		};
+		assert_eq!(game.players_len(), 0);
+
+		let ids = game.accept_pending();
+		assert_eq!(ids.len(), 2);
+		assert_eq!(game.players_len(), 2);
+		assert!(ids
+			.iter()
+			.all(|&id| game.ships().iter().any(|ship| ship.id == id)));
+
+		connections.try_send(stream).unwrap();
+		assert_eq!(game.accept_pending().len(), 1);
+		assert_eq!(game.players_len(), 3);
+	}
+
+	#[test]
+	fn ships_map_back_to_their_player() {
+		let mut game = Game::new();
+		let first = game.new_player(tcp_pair().0).unwrap().unwrap();
+		let second = game.new_player(tcp_pair().0).unwrap().unwrap();
+		let unowned = game.spawn_ship();
+
+		assert_eq!(game.player_for_ship(first), Some(0));
+		assert_eq!(game.player_for_ship(second), Some(1));
+		assert_eq!(game.player_for_ship(unowned), None);
+		assert_eq!(game.player_for_ship(ShipId(999)), None);
+	}
+
+	#[test]
+	fn heavier_ships_accelerate_slower_and_win_collisions() {
+		let physics = PhysicsConfig::default();
+		let mut light = Ship::default();
+		let mut heavy = Ship {
+			mass: 2.0,
+			..Default::default()
+		};
+		light.propulsor[0] = true;
+		heavy.propulsor[0] = true;
+		light.update(0.125, &physics);
+		heavy.update(0.125, &physics);
+		assert_eq!(light.acceleration, heavy.acceleration * 2.0);
+		assert!((light.velocity.y - 2.0 * heavy.velocity.y).abs() < 1e-9);
+
+		// Recoil pushes the heavy ship back half as much.
+		let mut heavy = Ship {
+			mass: 2.0,
+			..Default::default()
+		};
+		heavy.shoot(0.5);
+		assert_eq!(heavy.velocity, Vector64 { x: -0.25, y: 0.0 });
+
+		// Head on at the same speed, the light ship bounces back harder.
+		let mut game = Game::new();
+		game.set_ship_collision(true);
+		let (a, b) = (game.spawn_ship(), game.spawn_ship());
+		let ship = ship_mut(&mut game, a);
+		ship.position = Vector64 { x: -0.9, y: 0.0 };
+		ship.velocity = Vector64 { x: 1.0, y: 0.0 };
+		let ship = ship_mut(&mut game, b);
+		ship.position = Vector64 { x: 0.9, y: 0.0 };
+		ship.velocity = Vector64 { x: -1.0, y: 0.0 };
+		ship.mass = 2.0;
+		game.step(0.0);
+
+		let a = ship_mut(&mut game, a).velocity.x;
+		let b = ship_mut(&mut game, b).velocity.x;
+		assert!(a < -1.0);
+		assert!(b > -1.0 && b < 0.5);
+		// Momentum is kept.
+		assert!((a + 2.0 * b - (1.0 - 2.0)).abs() < 1e-9);
+	}
+
+	#[test]
+	fn full_tick_carries_input_to_the_broadcast() {
+		let mut game = Game::new();
+		let (stream, mut client) = tcp_pair();
+		let id = game.new_player(stream).unwrap().unwrap();
+		read_packet_bytes(&mut client);
+		send_input(
+			&mut client,
+			ClientData {
+				propulsor: [true, false, false, false],
+				sequence: 1,
+				..input(id, 1, 0.5)
+			},
+		);
+
+		assert_eq!(game.net_poll().packets_received, 1);
+		assert_eq!(game.apply_all_inputs(), 1);
+		game.step(0.125);
+		game.broadcast_state();
+
+		let state = decode_state(&read_packet_bytes(&mut client));
+		assert_eq!(state.tick, 1);
+		assert_eq!(state.last_processed_seq, 1);
+		let ship = &state.ships[0];
+		let expected = ship_mut(&mut game, id).position;
+		assert_eq!(ship.player_id, id.0 as u32);
+		assert_eq!(ship.propulsor, 0b0001);
+		assert!((ship.orientation - 0.5).abs() < 1e-5);
+		assert!(ship.position.y > 0.0);
+		assert_eq!(ship.position, Vector::from(expected));
+	}
+
+	#[test]
+	fn inputs_applied_after_step_wait_for_the_next_tick() {
+		let mut game = Game::new();
+		let id = game.spawn_ship();
+		game.step(0.125);
+		game.queue_input(ClientData {
+			propulsor: [true, false, false, false],
+			..input(id, 1, 0.0)
+		});
+		game.apply_all_inputs();
+		assert_eq!(ship_mut(&mut game, id).position, Vector64::default());
+
+		game.step(0.125);
+		assert!(ship_mut(&mut game, id).position.y > 0.0);
+	}
+
+	#[test]
+	fn gravity_pulls_ships_and_bullets() {
+		let physics = PhysicsConfig {
+			gravity: Vector { x: 0.0, y: -2.0 },
+			..Default::default()
+		};
+		let mut ship = Ship::default();
+		ship.update(0.125, &physics);
+		assert_eq!(ship.velocity, Vector64 { x: 0.0, y: -0.25 });
+		assert!(ship.position.y < 0.0 && ship.position.x == 0.0);
+
+		let mut bullet = Bullet::default();
+		bullet.update(0.125, &physics);
+		assert_eq!(bullet.velocity, Vector64 { x: 0.0, y: -0.25 });
+
+		// No gravity by default, a ship left alone stays put.
+		let mut ship = Ship::default();
+		ship.update(0.125, &PhysicsConfig::default());
+		assert_eq!(ship.position, Vector64::default());
+	}
+
+	#[test]
+	fn thrust_direction_follows_the_keys() {
+		let direction = |propulsor: [bool; 4]| {
+			Ship {
+				propulsor,
+				..Default::default()
+			}
+			.thrust_direction()
+		};
+
+		assert_eq!(direction([false; 4]), Vector { x: 0.0, y: 0.0 });
+		assert_eq!(
+			direction([true, false, false, false]),
+			Vector { x: 0.0, y: 1.0 }
+		);
+		assert_eq!(
+			direction([false, true, false, false]),
+			Vector { x: -1.0, y: 0.0 }
+		);
+		assert_eq!(
+			direction([false, false, true, false]),
+			Vector { x: 0.0, y: -1.0 }
+		);
+		assert_eq!(
+			direction([false, false, false, true]),
+			Vector { x: 1.0, y: 0.0 }
+		);
+		assert_eq!(
+			direction([true, false, false, true]),
+			Vector { x: 1.0, y: 1.0 }
+		);
+		// Opposite keys cancel out.
+		assert_eq!(
+			direction([true, true, true, false]),
+			Vector { x: -1.0, y: 0.0 }
+		);
+	}
+
+	#[test]
+	fn messages_split_across_reads_are_reassembled() {
+		let mut game = Game::new();
+		let stream = MockStream::default();
+		let id = game.new_player(stream.clone()).unwrap().unwrap();
+		let welcome = stream.take_written();
+		assert_eq!(decode_header(&welcome).unwrap().0, WelcomePacket::id());
+		let packet = PacketProtocol::Zero(ClientPacket::from(&input(id, 1, 2.0)))
+			.serialize()
+			.unwrap();
+
+		// Half a header, the rest of it, the first content bytes, then the others.
+		let (header, content) = packet.split_at(HEADER_LEN);
+		for part in [&header[..5], &header[5..], &content[..3]] {
+			stream.push(part);
+			assert_eq!(game.net_poll().packets_received, 0);
+		}
+		stream.push(&content[3..]);
+		assert_eq!(game.net_poll().packets_received, 1);
+
+		assert_eq!(game.apply_all_inputs(), 1);
+		assert!((ship_mut(&mut game, id).orientation - 2.0).abs() < 1e-5);
+
+		// A closed stream drops the player.
+		stream.close();
+		assert_eq!(game.net_poll().removed_ships, vec![id]);
+	}
+
+	#[test]
+	fn important_packets_go_first_and_states_do_not_pile_up() {
+		let mut game = Game::new();
+		let stream = MockStream::default();
+		let id = game.new_player(stream.clone()).unwrap().unwrap();
+		stream.take_written();
+
+		// A stalled connection: the first state is stuck unsent, the others queue up.
+		stream.set_write_budget(Some(0));
+		for _ in 0..5 {
+			game.step(0.125);
+			assert!(game.broadcast_state().is_empty());
+		}
+		let welcome = PacketProtocol::Zero(game.welcome_packet(id))
+			.serialize()
+			.unwrap();
+		game.players[0].queue(&welcome, WelcomePacket::priority());
+		// The latest state and the welcome.
+		assert_eq!(game.players[0].queued.len(), 2);
+
+		stream.set_write_budget(None);
+		game.players[0].flush().unwrap();
+		let packets = stream.take_packets();
+		let ids: Vec<u32> = packets.iter().map(|packet| packet.0).collect();
+		assert_eq!(
+			ids,
+			[ServerPacket::id(), WelcomePacket::id(), ServerPacket::id()]
+		);
+		assert_eq!(StateMessage::decode(&packets[0].2).unwrap().tick, 1);
+		assert_eq!(StateMessage::decode(&packets[2].2).unwrap().tick, 5);
+	}
+
+	#[test]
+	fn new_players_join_the_smaller_team() {
+		let mut game = Game::new();
+		assert_eq!(game.suggest_team(), None);
+
+		game.set_team_count(3);
+		for team in [0, 0, 1, 2, 2] {
+			let id = game.spawn_ship();
+			ship_mut(&mut game, id).team = team;
+		}
+		let counts = game.ship_count_by_team();
+		assert_eq!((counts[&0], counts[&1], counts[&2]), (2, 1, 2));
+		assert_eq!(game.suggest_team(), Some(1));
+
+		let id = game.new_player(MockStream::default()).unwrap().unwrap();
+		assert_eq!(ship_mut(&mut game, id).team, 1);
+		// All even again, ties go to the lowest team.
+		assert_eq!(game.suggest_team(), Some(0));
+	}
+
+	#[test]
+	fn bots_target_the_nearest_enemy() {
+		let mut game = Game::new();
+		game.set_team_count(2);
+		let bot = game.add_bot();
+		let teammate = game.spawn_ship();
+		let enemy = game.spawn_ship();
+		ship_mut(&mut game, teammate).position = Vector64 { x: 0.0, y: 2.0 };
+		let ship = ship_mut(&mut game, enemy);
+		ship.position = Vector64 { x: 0.0, y: -20.0 };
+		ship.team = 1;
+
+		let bot_ship = game.ships()[0].clone();
+		assert_eq!(game.nearest_enemy(&bot_ship).unwrap().id, enemy);
+		game.step(0.1);
+		// Turning toward the enemy below, not the teammate above.
+		assert!(ship_mut(&mut game, bot).orientation < 0.0);
+
+		// Without teams every other ship is an enemy.
+		game.set_team_count(0);
+		assert_eq!(game.nearest_enemy(&bot_ship).unwrap().id, teammate);
+	}
+
+	#[test]
+	fn same_seed_gives_the_same_spawns() {
+		let spawns = |seed: u64| {
+			let mut game = Game::with_config(GameConfig {
+				seed,
+				..Default::default()
+			})
+			.unwrap();
+			game.set_random_spawns(true);
+			for _ in 0..5 {
+				game.spawn_ship();
+			}
+			let mut positions: Vec<Vector64> =
+				game.ships().iter().map(|ship| ship.position).collect();
+			// Respawns draw from the same rng.
+			game.reset_round(false);
+			positions.extend(game.ships().iter().map(|ship| ship.position));
+			positions
+		};
+
+		let spawns_a = spawns(180);
+		assert_eq!(spawns_a, spawns(180));
+		assert_ne!(spawns_a, spawns(181));
+		let (min, max) = Game::new().world_bounds();
+		for position in &spawns_a {
+			let position = Vector::from(*position);
+			assert!(position.x >= min.x && position.x <= max.x);
+			assert!(position.y >= min.y && position.y <= max.y);
+		}
+		// Spread apart, not stacked at the center.
+		assert!(spawns_a.windows(2).all(|pair| pair[0] != pair[1]));
+	}
+
+	#[test]
+	fn piercing_bullets_pass_through_ships_once() {
+		let mut game = Game::new();
+		game.set_spawn_invulnerability(0);
+		let ids: Vec<ShipId> = [3.0, 6.0, 9.0]
+			.into_iter()
+			.map(|x| {
+				let id = game.spawn_ship();
+				ship_mut(&mut game, id).position = Vector64 { x, y: 0.0 };
+				id
+			})
+			.collect();
+		game.bullets.push(Bullet {
+			owner_id: ShipId(99),
+			pierces_remaining: 2,
+			..bullet_of(BulletKind::Standard)
+		});
+
+		// Each ship is overlapped for several steps but only hit once.
+		for _ in 0..40 {
+			game.step(0.025);
+		}
+		let hits: Vec<i32> = ids.iter().map(|&id| ship_mut(&mut game, id).hits).collect();
+		assert_eq!(hits, vec![1, 1, 1]);
+		// The third ship stopped it.
+		assert!(game.bullets().is_empty());
+	}
+
+	#[test]
+	fn hostile_inputs_are_rejected_or_sanitized() {
+		let mut game = Game::new();
+		let stream = MockStream::default();
+		let id = game.new_player(stream.clone()).unwrap().unwrap();
+		let send = |client_tick: u32, orientation: f32, propulsor: u8| {
+			let packet = ClientPacket {
+				player_id: id.0 as u32,
+				orientation,
+				propulsor,
+				client_tick,
+				sequence: client_tick,
+			};
+			stream.push(&PacketProtocol::Zero(packet).serialize().unwrap());
+		};
+
+		send(1, f32::NAN, 0b0001);
+		game.net_poll();
+		assert_eq!(game.apply_all_inputs(), 0);
+		assert_eq!(game.rejected_inputs(), 1);
+		let ship = ship_mut(&mut game, id);
+		assert_eq!((ship.orientation, ship.propulsor), (0.0, [false; 4]));
+
+		// Only the low four propulsor bits mean anything, the orientation is wrapped.
+		send(2, 7.0, 0b1111_0010);
+		game.net_poll();
+		assert_eq!(game.apply_all_inputs(), 1);
+		let ship = ship_mut(&mut game, id);
+		assert_eq!(ship.propulsor, [false, true, false, false]);
+		assert!((ship.orientation - (7.0 - core::f32::consts::TAU)).abs() < 1e-5);
+		assert_eq!(game.rejected_inputs(), 1);
+	}
+
+	#[test]
+	fn fixed_steps_ignore_frame_timing() {
+		let run = |frames: &[f32]| {
+			let mut game = Game::with_config(GameConfig {
+				tick_dt: Some(0.125),
+				..Default::default()
+			})
+			.unwrap();
+			let id = game.spawn_ship();
+			ship_mut(&mut game, id).propulsor[0] = true;
+			for &frame in frames {
+				game.advance(frame);
+			}
+			(game.tick(), ship_mut(&mut game, id).position)
+		};
+
+		// Eight steps however the second is cut into frames, none behind enough to hit the step cap.
+		let (ticks, position) = run(&[0.25, 0.25, 0.5]);
+		assert_eq!(ticks, 8);
+		assert_eq!(run(&[0.5, 0.5]), (ticks, position));
+		assert_eq!(run(&[0.1, 0.3, 0.125, 0.475]), (ticks, position));
+
+		// Constant thrust from rest: every step moves by v * dt / 2 + a * dt^2, with v = k * a * dt
+		// after k steps.
+		let (n, dt, a) = (8.0, 0.125, Ship::THRUST as f64);
+		let expected = a * dt * dt * (n * (n - 1.0) / 4.0 + n);
+		assert_eq!(
+			position,
+			Vector64 {
+				x: 0.0,
+				y: expected
+			}
+		);
+
+		for tick_dt in [0.0, -0.125, f32::NAN, f32::INFINITY] {
+			assert!(Game::with_config(GameConfig {
+				tick_dt: Some(tick_dt),
+				..Default::default()
+			})
+			.is_err());
+		}
+	}
+
+	#[test]
+	fn respawned_ships_shrug_off_bullets_for_a_while() {
+		for mode in [
+			InvulnerabilityMode::PassThrough,
+			InvulnerabilityMode::Absorb,
+		] {
+			let mut game = Game::new();
+			game.set_spawn_invulnerability(3);
+			game.set_invulnerability_mode(mode);
+			let id = game.spawn_ship();
+			game.reset_round(false);
+			assert!(game.snapshot().ships[0].invulnerable);
+
+			// A bullet sitting on the ship every step, until the window is over.
+			let mut hit_at = None;
+			for _ in 0..5 {
+				game.bullets.push(Bullet {
+					owner_id: ShipId(99),
+					velocity: Vector64::default(),
+					..bullet_of(BulletKind::Standard)
+				});
+				game.step(0.125);
+				let ship = ship_mut(&mut game, id);
+				if ship.damage > 0.0 {
+					hit_at = Some(game.tick());
+					break;
+				}
+				assert_eq!(ship.velocity, Vector64::default());
+			}
+			assert_eq!(hit_at, Some(3));
+			assert!(!game.snapshot().ships[0].invulnerable);
+			let state = decode_state(&game.send_server_packet(1, 0, 0));
+			assert!(!state.ships[0].invulnerable);
+
+			// Passing bullets pile up on the ship and all hit once they can, absorbed ones are gone.
+			let hits = ship_mut(&mut game, id).hits;
+			match mode {
+				InvulnerabilityMode::PassThrough => assert_eq!(hits, 3),
+				InvulnerabilityMode::Absorb => assert_eq!(hits, 1),
+			}
+			assert!(game.bullets().is_empty());
+		}
+	}
+
+	#[test]
+	fn stats_reflect_the_game() {
+		let mut game = Game::new();
+		let stream = MockStream::default();
+		let id = game.new_player(stream.clone()).unwrap().unwrap();
+		game.spawn_ship();
+		game.add_bot();
+		game.set_bot_ai(false);
+		game.fire(id, BulletKind::Standard).unwrap();
+		game.step(0.125);
+		game.step(0.125);
+		stream.take_written();
+
+		game.send_stats(0).unwrap();
+		let packets = stream.take_packets();
+		let stats: StatsPacket = decode(&packets[0]);
+		assert_eq!(
+			(stats.players, stats.ships, stats.bullets, stats.tick),
+			(1, 3, 1, 2)
+		);
+		assert!(stats.tick_ms >= 0.0 && stats.tick_ms < 1000.0);
+
+		let metrics = GameMetrics {
+			step_time: Duration::from_micros(2_500),
+			..game.metrics()
+		};
+		assert_eq!(StatsPacket::from(&metrics).tick_ms, 2.5);
+		assert!(game.send_stats(1).is_err());
+	}
+
+	#[test]
+	fn handle_packet_routes_by_id() {
+		let mut game = Game::new();
+		let stream = MockStream::default();
+		let id = game.new_player(stream.clone()).unwrap().unwrap();
+		let other = game.spawn_ship();
+		stream.take_written();
+
+		// Input for the player's own ship is queued, for any other ship refused.
+		let own = PacketProtocol::Zero(ClientPacket::from(&input(id, 1, 1.5)));
+		game.handle_packet(0, own).unwrap();
+		let foreign = PacketProtocol::Zero(ClientPacket::from(&input(other, 1, 1.5)));
+		assert!(game.handle_packet(0, foreign).is_err());
+		assert_eq!(game.apply_all_inputs(), 1);
+		assert!((ship_mut(&mut game, id).orientation - 1.5).abs() < 1e-5);
+
+		// Pings are answered right away, in the ping's protocol.
+		let ping = PacketProtocol::Json(PingPacket { client_time: 77 });
+		game.handle_packet(0, ping).unwrap();
+		let packets = stream.take_packets();
+		assert_eq!(packets.len(), 1);
+		assert_eq!(packets[0].1, 1);
+		let pong: PongPacket = decode(&packets[0]);
+		assert_eq!(pong.client_time, 77);
+
+		// Acks only ever move forward.
+		for tick in [5, 3] {
+			game.handle_packet(0, PacketProtocol::Zero(AckPacket { tick }))
+				.unwrap();
+		}
+		assert_eq!(game.last_acked_tick(0), Some(5));
+
+		assert!(game
+			.handle_packet(0, PacketProtocol::Zero(ServerPacket::default()))
+			.is_err());
+		assert!(game
+			.handle_packet(1, PacketProtocol::Zero(AckPacket { tick: 1 }))
+			.is_err());
+
+		// Received packets that fail to route are counted and do not stop the others.
+		let garbage = PacketProtocol::Raw {
+			id: AckPacket::id(),
+			protocol: 0,
+			content: vec![1],
+		};
+		let own = PacketProtocol::Zero(ClientPacket::from(&input(id, 2, 0.5)));
+		game.players[0].inbound.extend([garbage, own]);
+		assert_eq!(game.apply_all_inputs(), 1);
+		assert_eq!(game.malformed_packets(), 1);
+	}
+
+	#[test]
+	fn destroyed_ships_cannot_fire_or_steer() {
+		let mut game = Game::new();
+		let id = game.spawn_ship();
+		let ship = ship_mut(&mut game, id);
+		ship.damage = ship.max_health;
+
+		assert_eq!(game.fire(id, BulletKind::Standard), None);
+		assert!(game
+			.fire_weapon(id, &Weapon::spread(BulletKind::Fast, 3, 0.5))
+			.is_empty());
+		assert!(!game.apply_input(ClientData {
+			propulsor: [true; 4],
+			..input(id, 1, 1.0)
+		}));
+		assert!(game.bullets().is_empty());
+		let ship = game.ship(id).unwrap();
+		assert_eq!((ship.orientation, ship.propulsor), (0.0, [false; 4]));
+
+		// The step removes it.
+		assert_eq!(game.step(0.125).ships, vec![id]);
+	}
+
+	#[test]
+	fn ships_in_aabb_keeps_only_the_inside() {
+		let mut game = Game::new();
+		let positions = [(0.0, 0.0), (5.0, 5.0), (-5.0, 2.0), (50.0, 0.0), (5.0, 5.1)];
+		let ids: Vec<ShipId> = positions
+			.into_iter()
+			.map(|(x, y)| {
+				let id = game.spawn_ship();
+				ship_mut(&mut game, id).position = Vector64 { x, y };
+				id
+			})
+			.collect();
+
+		let inside = game.ships_in_aabb(Vector { x: -5.0, y: -1.0 }, Vector { x: 5.0, y: 5.0 });
+		// Edges included, in spawn order.
+		assert_eq!(inside, vec![ids[0], ids[1], ids[2]]);
+
+		let everything = Vector { x: 1e30, y: 1e30 };
+		assert_eq!(game.ships_in_aabb(-everything, everything), ids);
+		assert!(game
+			.ships_in_aabb(Vector { x: 1.0, y: 1.0 }, Vector { x: 2.0, y: 2.0 })
+			.is_empty());
+	}
+
+	#[test]
+	fn ships_spawn_on_request_and_leave_is_reported() {
+		let mut game = Game::new();
+		game.set_spawn_on_connect(false);
+		let stream = MockStream::default();
+		assert_eq!(game.new_player(stream.clone()).unwrap(), None);
+		game.net_poll();
+		game.apply_all_inputs();
+		assert_eq!(game.ships_len(), 0);
+		assert!(stream.take_packets().is_empty());
+
+		let control = |control: ControlPacket| {
+			stream.push(&PacketProtocol::Zero(control).serialize().unwrap());
+		};
+		control(ControlPacket::RequestSpawn {
+			design: 2,
+			team: None,
+		});
+		game.net_poll();
+		assert_eq!(game.ships_len(), 0);
+		game.apply_all_inputs();
+		assert_eq!(game.ships_len(), 1);
+		let welcome: WelcomePacket = decode(&stream.take_packets()[0]);
+		let id = ShipId(welcome.your_ship_id as i32);
+		assert_eq!(game.ship(id).unwrap().design, 2);
+		assert_eq!(game.player_for_ship(id), Some(0));
+
+		control(ControlPacket::Leave);
+		game.net_poll();
+		game.apply_all_inputs();
+		assert!(game.ship(id).is_none());
+		assert_eq!(game.players_len(), 1);
+		assert_eq!(game.step(0.125).ships, vec![id]);
+		assert_eq!(game.step(0.125), RemovedEntities::default());
+	}
+
+	#[test]
+	fn corrupted_streams_resync_instead_of_disconnecting() {
+		let mut game = Game::new();
+		let stream = MockStream::default();
+		let id = game.new_player(stream.clone()).unwrap().unwrap();
+		let packet = |client_tick: u32| {
+			let input = ClientData {
+				sequence: client_tick,
+				..input(id, client_tick, 0.5)
+			};
+			PacketProtocol::Zero(ClientPacket::from(&input))
+				.serialize()
+				.unwrap()
+		};
+
+		// Junk, a header announcing far too much content, then two good packets together.
+		stream.push(&[0xde, 0xad, 0xbe, 0xef, 0xba, 0x00]);
+		stream.push(&encode_header(ClientPacket::id(), 0, u32::MAX));
+		stream.push(&packet(1));
+		stream.push(&[1, 2, 3]);
+		stream.push(&packet(2));
+		let report = game.net_poll();
+		assert!(report.disconnected.is_empty());
+		assert_eq!(report.packets_received, 2);
+		assert_eq!(game.players_len(), 1);
+		assert_eq!(game.malformed_packets(), 1);
+
+		assert_eq!(game.apply_all_inputs(), 1);
+		assert_eq!(game.apply_all_inputs(), 1);
+		assert_eq!(game.players[0].last_processed_seq, 2);
+	}
+
+	#[test]
+	fn rapid_fire_overheats_until_cooled_down() {
+		let mut game = Game::new();
+		game.set_physics(PhysicsConfig {
+			heat: Some(HeatConfig::default()),
+			..Default::default()
+		});
+		let id = game.spawn_ship();
+
+		// Ten shots reach the maximum heat, the next one is refused.
+		for _ in 0..10 {
+			assert!(game.fire(id, BulletKind::Standard).is_some());
+		}
+		assert!(game.ship(id).unwrap().overheated);
+		assert_eq!(game.fire(id, BulletKind::Standard), None);
+
+		// Cooling to exactly the recovery threshold is not enough.
+		for _ in 0..10 {
+			game.step(0.125);
+		}
+		assert_eq!(game.ship(id).unwrap().heat, 5.0);
+		assert_eq!(game.fire(id, BulletKind::Standard), None);
+
+		game.step(0.125);
+		assert!(!game.ship(id).unwrap().overheated);
+		assert!(game.fire(id, BulletKind::Standard).is_some());
+		assert_eq!(game.ship(id).unwrap().heat, 5.5);
+	}
+
+	#[test]
+	fn turn_rate_spreads_a_half_turn_over_ticks() {
+		let mut game = Game::new();
+		game.set_physics(PhysicsConfig {
+			max_turn_rate: Some(core::f32::consts::PI),
+			..Default::default()
+		});
+		let id = game.spawn_ship();
+		// Just short of a half turn, so the shorter way around is counter clockwise.
+		let target = 3.0;
+		assert!(game.apply_input(input(id, 1, target)));
+		assert_eq!(game.ship(id).unwrap().orientation, 0.0);
+
+		let mut ticks = 0;
+		let mut previous = 0.0;
+		while game.ship(id).unwrap().target_orientation.is_some() {
+			game.step(0.125);
+			ticks += 1;
+			let orientation = game.ship(id).unwrap().orientation;
+			assert!(orientation > previous);
+			assert!(orientation - previous <= core::f32::consts::PI * 0.125 + 1e-5);
+			previous = orientation;
+			assert!(ticks <= 8);
+		}
+		// At most PI / 8 per tick, 3 radians need eight ticks.
+		assert_eq!(ticks, 8);
+		assert!((game.ship(id).unwrap().orientation - target).abs() < 1e-5);
+
+		// From 3 to -3 the shorter way goes through PI and fits in a single tick.
+		assert!(game.apply_input(input(id, 2, -3.0)));
+		game.step(0.125);
+		let ship = game.ship(id).unwrap();
+		assert!((ship.orientation + 3.0).abs() < 1e-5);
+		assert_eq!(ship.target_orientation, None);
+	}
+
+	#[test]
+	fn obstacles_stop_ships_and_bullets() {
+		let json = r#"{"obstacles": [
+			{"shape": {"Aabb": {"min": {"x": 103.0, "y": 90.0}, "max": {"x": 110.0, "y": 110.0}}}}
+		]}"#;
+		let map = Map::load(json.as_bytes()).unwrap();
+		let mut game = Game::new();
+		game.load_map(map);
+		assert_eq!(game.obstacles().len(), 1);
+
+		let id = game.spawn_ship();
+		let ship = ship_mut(&mut game, id);
+		ship.position = Vector64 { x: 100.0, y: 100.0 };
+		ship.velocity = Vector64 { x: 10.0, y: 0.0 };
+		game.bullets.push(Bullet {
+			position: Vector64 { x: 105.0, y: 105.0 },
+			..bullet_of(BulletKind::Standard)
+		});
+		let bullet = game.bullets[0].id;
+
+		let removed = game.step(0.1);
+		assert_eq!(removed.bullets, vec![bullet]);
+		assert!(game.bullets.is_empty());
+
+		for _ in 0..20 {
+			game.step(0.1);
+		}
+		let ship = game.ship(id).unwrap();
+		assert!(ship.position.x + Ship::RADIUS as f64 <= 103.0 + 1e-6);
+		assert!(ship.velocity.x <= 0.0);
+		assert_eq!(ship.position.y, 100.0);
+	}
+
+	#[test]
+	fn broadcasts_every_third_tick() {
+		let mut game = Game::new();
+		game.set_broadcast_interval(3);
+		let stream = MockStream::default();
+		game.new_player(stream.clone()).unwrap();
+		stream.take_packets();
+
+		let mut due = Vec::new();
+		for _ in 0..60 {
+			game.step(1.0 / 60.0);
+			due.push(game.broadcast_due());
+			game.broadcast_state();
+		}
+
+		let states = stream
+			.take_packets()
+			.into_iter()
+			.filter(|(id, _, _)| *id == ServerPacket::id())
+			.count();
+		assert_eq!(states, 20);
+		assert_eq!(due.iter().filter(|due| **due).count(), 20);
+		assert_eq!(&due[..3], &[false, false, true]);
+	}
+}
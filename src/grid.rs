@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+
+use crate::vector::Vector;
+
+// Uniform grid bucketing entities by cell, so proximity queries only visit nearby cells.
+pub struct SpatialGrid<K> {
+	cell_size: f32,
+	cells: HashMap<(i32, i32), Vec<K>>,
+}
+
+impl<K: Copy> SpatialGrid<K> {
+	pub fn new(cell_size: f32) -> Self {
+		Self {
+			cell_size,
+			cells: HashMap::new(),
+		}
+	}
+
+	pub fn insert(&mut self, key: K, position: Vector) {
+		let cell = self.cell(position);
+		self.cells.entry(cell).or_default().push(key);
+	}
+
+	// Returns every key in a cell touching the circle. Callers still need to check the
+	// actual distance, a candidate may be up to one cell away from the circle.
+	pub fn query(&self, position: Vector, radius: f32) -> Vec<K> {
+		let extent = Vector {
+			x: radius,
+			y: radius,
+		};
+		self.query_aabb(position - extent, position + extent)
+	}
+
+	// Returns every key in a cell overlapping the box from `min` to `max`. As with `query`,
+	// candidates near the edges may lie outside the box itself.
+	pub fn query_aabb(&self, min: Vector, max: Vector) -> Vec<K> {
+		let (min_x, min_y) = self.cell(min);
+		let (max_x, max_y) = self.cell(max);
+
+		let mut keys = Vec::new();
+		// A box spanning more cells than are occupied is cheaper to answer from the occupied
+		// ones. They are visited in the same order as the loops below.
+		let span = |min: i32, max: i32| (max as i64 - min as i64 + 1).max(0) as u64;
+		if span(min_x, max_x).saturating_mul(span(min_y, max_y)) > self.cells.len() as u64 {
+			let mut cells: Vec<_> = self
+				.cells
+				.iter()
+				.filter(|((x, y), _)| (min_x..=max_x).contains(x) && (min_y..=max_y).contains(y))
+				.collect();
+			cells.sort_unstable_by_key(|(cell, _)| **cell);
+			for (_, cell) in cells {
+				keys.extend_from_slice(cell);
+			}
+			return keys;
+		}
+
+		for x in min_x..=max_x {
+			for y in min_y..=max_y {
+				if let Some(cell) = self.cells.get(&(x, y)) {
+					keys.extend_from_slice(cell);
+				}
+			}
+		}
+
+		keys
+	}
+
+	fn cell(&self, position: Vector) -> (i32, i32) {
+		(
+			(position.x / self.cell_size).floor() as i32,
+			(position.y / self.cell_size).floor() as i32,
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn grid() -> SpatialGrid<u32> {
+		let mut grid = SpatialGrid::new(10.0);
+		grid.insert(0, Vector { x: 5.0, y: 5.0 });
+		grid.insert(1, Vector { x: 15.0, y: 5.0 });
+		grid.insert(2, Vector { x: -25.0, y: 35.0 });
+		grid.insert(3, Vector { x: 5.0, y: 8.0 });
+		grid
+	}
+
+	#[test]
+	fn query_aabb_visits_overlapping_cells() {
+		let grid = grid();
+		// Three cells against three occupied ones, walked cell by cell.
+		let keys = grid.query_aabb(Vector { x: 1.0, y: 1.0 }, Vector { x: 29.0, y: 9.0 });
+		assert_eq!(keys, vec![0, 3, 1]);
+		assert!(grid
+			.query_aabb(Vector { x: 40.0, y: 40.0 }, Vector { x: 45.0, y: 45.0 })
+			.is_empty());
+	}
+
+	#[test]
+	fn huge_boxes_only_visit_occupied_cells() {
+		let grid = grid();
+		let all = grid.query_aabb(Vector { x: -1e30, y: -1e30 }, Vector { x: 1e30, y: 1e30 });
+		// Same order as walking the cells: by column, then by row.
+		assert_eq!(all, vec![2, 0, 3, 1]);
+
+		let half = grid.query_aabb(Vector { x: 0.0, y: -1e9 }, Vector { x: 1e9, y: 1e9 });
+		assert_eq!(half, vec![0, 3, 1]);
+		// Inverted boxes cover nothing.
+		assert!(grid
+			.query_aabb(Vector { x: 1e9, y: 0.0 }, Vector { x: -1e9, y: 0.0 })
+			.is_empty());
+	}
+
+	#[test]
+	fn both_paths_agree() {
+		let mut grid = SpatialGrid::new(1.0);
+		for key in 0..50 {
+			let key = key as u32;
+			grid.insert(
+				key,
+				Vector {
+					x: (key % 7) as f32 * 1.5,
+					y: (key / 7) as f32,
+				},
+			);
+		}
+		let (min, max) = (Vector { x: 1.0, y: 1.0 }, Vector { x: 6.0, y: 4.0 });
+		let walked = grid.query_aabb(min, max);
+
+		// Few occupied cells make the same box go through the occupied cell path.
+		let mut sparse = SpatialGrid::new(1.0);
+		for key in walked.iter().copied() {
+			sparse.insert(
+				key,
+				Vector {
+					x: (key % 7) as f32 * 1.5,
+					y: (key / 7) as f32,
+				},
+			);
+		}
+		assert_eq!(sparse.query_aabb(min, max), walked);
+		assert!(!walked.is_empty());
+	}
+}
@@ -0,0 +1,12 @@
+pub mod client;
+pub mod collider;
+pub mod config;
+pub mod game;
+pub mod grid;
+pub mod map;
+pub mod protocol;
+pub mod replay;
+pub mod rng;
+pub mod tick;
+pub mod vector;
+pub mod web;
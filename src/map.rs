@@ -0,0 +1,29 @@
+use std::io::Read;
+
+use serde::{Deserialize, Serialize};
+
+use crate::collider::{Collider, Shape};
+
+// Static piece of the arena, ships slide along it and bullets stop on it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Obstacle {
+	pub shape: Shape,
+}
+
+impl Collider for Obstacle {
+	fn shape(&self) -> Shape {
+		self.shape
+	}
+}
+
+// Arena layout, written by hand as JSON.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Map {
+	pub obstacles: Vec<Obstacle>,
+}
+
+impl Map {
+	pub fn load<R: Read>(reader: R) -> anyhow::Result<Self> {
+		Ok(serde_json::from_reader(reader)?)
+	}
+}
@@ -1,13 +1,54 @@
+use std::fmt;
+use std::io::{Read, Write};
+
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use crate::vector::Vector;
 
 const ZERO_PROTOCOL_ID: u8 = 0x00;
 const JSON_PROTOCOL_ID: u8 = 0x01;
+const ZERO_VARINT_PROTOCOL_ID: u8 = 0x02;
+
+pub const HEADER_LEN: usize = HeaderFormat::DEFAULT.len;
+
+// Starts every header, so a reader that lost track of packet boundaries can find the next one.
+pub const SYNC_WORD: [u8; 2] = [0xBA, 0x11];
+
+// Largest content a header may announce, anything above is rejected before allocating for it.
+pub const MAX_CONTENT_LENGTH: u32 = 1 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtocolError {
+	TooLarge { length: u32, max: u32 },
+}
+
+impl fmt::Display for ProtocolError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			ProtocolError::TooLarge { length, max } => {
+				write!(f, "Content length {length} over the maximum of {max}")
+			}
+		}
+	}
+}
+
+impl std::error::Error for ProtocolError {}
+
+pub fn check_content_length(length: u32) -> Result<(), ProtocolError> {
+	if length > MAX_CONTENT_LENGTH {
+		return Err(ProtocolError::TooLarge {
+			length,
+			max: MAX_CONTENT_LENGTH,
+		});
+	}
+
+	Ok(())
+}
 
 pub struct PacketBuf {
 	buf: Vec<u8>,
 	state: PacketBufState,
+	format: HeaderFormat,
 }
 
 enum PacketBufState {
@@ -24,98 +65,353 @@ pub enum PacketProtocol<T: Packet> {
 	},
 	Zero(T),
 	Json(T),
+	// Zero protocol with variable length integers, smaller for small values.
+	ZeroVarint(T),
 }
 
+// Send priorities, higher goes out first when packets queue up for a slow connection.
+pub const PRIORITY_LOW: u8 = 0;
+pub const PRIORITY_NORMAL: u8 = 1;
+pub const PRIORITY_HIGH: u8 = 2;
+
 pub trait Packet: Serialize + DeserializeOwned {
 	fn id() -> u32;
+
+	fn priority() -> u8 {
+		PRIORITY_NORMAL
+	}
+}
+
+// Turns packet content into bytes and back, tagged with the protocol id it is sent under.
+// `PacketProtocol` variants use the built in codecs, any other one goes through
+// `PacketProtocol::serialize_with` and `deserialize_with`.
+pub trait WireCodec {
+	fn protocol_id(&self) -> u8;
+	// Appends the encoded value to `out`.
+	fn encode_into<T: Serialize>(&self, value: &T, out: &mut Vec<u8>) -> anyhow::Result<()>;
+	fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> anyhow::Result<T>;
+
+	// Exact encoded size, for codecs that can tell without encoding.
+	fn size_hint<T: Serialize>(&self, _value: &T) -> Option<usize> {
+		None
+	}
+
+	fn encode<T: Serialize>(&self, value: &T) -> anyhow::Result<Vec<u8>> {
+		let mut out = Vec::with_capacity(self.size_hint(value).unwrap_or(0));
+		self.encode_into(value, &mut out)?;
+		Ok(out)
+	}
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+// Bincode, big-endian with fixed size integers.
+pub struct ZeroCodec;
+
+// Bincode, big-endian with variable length integers.
+pub struct ZeroVarintCodec;
+
+pub struct JsonCodec;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct ClientPacket {
 	pub player_id: u32,
-	pub orientation: u32,
+	pub orientation: f32,
 	pub propulsor: u8,
+	pub client_tick: u32,
+	// Echoed back by the server once applied, for client side reconciliation.
+	pub sequence: u32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct ServerPacket {
 	pub player_id: u32,
 	pub position: Vector,
 	pub velocity: Vector,
-	pub orientation: u32,
+	pub orientation: f32,
 	pub design: u8,
 	pub propulsor: u8,
 	pub hits: u32,
+	// Just spawned, bullets pass through it.
+	pub invulnerable: bool,
 }
 
-impl PacketBuf {
-	const HEADER_LEN: usize = 9;
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BulletState {
+	pub id: u32,
+	pub position: Vector,
+	pub velocity: Vector,
+	pub owner_id: i32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WorldSnapshot {
+	pub ships: Vec<ServerPacket>,
+	pub bullets: Vec<BulletState>,
+	pub tick: u64,
+}
+
+// World state as broadcast by the server, sent under the `ServerPacket` id:
+// [ 64 bits |        32 bits          |  32 bits   | ship count * ship |   32 bits    | bullet count * bullet ]
+// [  tick   | last processed sequence | ship count |       ships       | bullet count |        bullets        ]
+// Every ship and bullet is laid out like a Zero protocol `ServerPacket` and `BulletState`.
+#[derive(Debug, Clone)]
+pub struct StateMessage {
+	pub tick: u64,
+	pub last_processed_seq: u32,
+	pub ships: Vec<ServerPacket>,
+	pub bullets: Vec<BulletState>,
+}
+
+impl StateMessage {
+	pub fn decode(mut bytes: &[u8]) -> anyhow::Result<Self> {
+		use bincode::Options;
+
+		let options = bincode::options().with_big_endian().with_fixint_encoding();
+		let tick = options.deserialize_from(&mut bytes)?;
+		let last_processed_seq = options.deserialize_from(&mut bytes)?;
+
+		let ship_count: u32 = options.deserialize_from(&mut bytes)?;
+		let ships = (0..ship_count)
+			.map(|_| options.deserialize_from(&mut bytes))
+			.collect::<Result<_, _>>()?;
+
+		let bullet_count: u32 = options.deserialize_from(&mut bytes)?;
+		let bullets = (0..bullet_count)
+			.map(|_| options.deserialize_from(&mut bytes))
+			.collect::<Result<_, _>>()?;
+
+		if !bytes.is_empty() {
+			anyhow::bail!("Trailing bytes after state message");
+		}
+
+		Ok(StateMessage {
+			tick,
+			last_processed_seq,
+			ships,
+			bullets,
+		})
+	}
+}
+
+// Sent once to a client right after it joins.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WelcomePacket {
+	pub your_ship_id: u32,
+	pub world_width: f32,
+	pub world_height: f32,
+	pub tick_rate: f32,
+	// Physics constants for client side prediction.
+	pub thrust: f32,
+	pub recoil: f32,
+}
+
+// Live server figures for an admin or spectator connection.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StatsPacket {
+	pub players: u32,
+	pub ships: u32,
+	pub bullets: u32,
+	pub tick: u64,
+	// Time the last step took.
+	pub tick_ms: f32,
+}
+
+// Latency probe, the server answers with a `PongPacket` carrying the same time.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PingPacket {
+	pub client_time: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PongPacket {
+	pub client_time: u64,
+}
 
+// Explicit spawn and leave requests, for players who join without a ship.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum ControlPacket {
+	// Without a team the server picks one.
+	RequestSpawn {
+		design: u8,
+		team: Option<u8>,
+	},
+	// Gives up the player's ships, the connection stays open.
+	#[default]
+	Leave,
+}
+
+// Tells the server the client got the state of `tick`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AckPacket {
+	pub tick: u64,
+}
+
+impl PacketBuf {
 	pub fn new() -> Self {
+		Self::with_format(HeaderFormat::DEFAULT)
+	}
+
+	// Reassembles packets framed with the given header layout.
+	pub fn with_format(format: HeaderFormat) -> Self {
 		Self {
 			buf: vec![],
 			state: PacketBufState::Header,
+			format,
 		}
 	}
 
-	pub fn process<T: Packet>(&mut self, bytes: &[u8]) -> Option<PacketProtocol<T>> {
+	// Buffers `bytes` and returns the next complete packet, if any. A header without the sync
+	// word is skipped through `resync`. Fails on a header announcing more than
+	// `MAX_CONTENT_LENGTH`, that header is skipped so later calls carry on after it.
+	pub fn process<T: Packet>(
+		&mut self,
+		bytes: &[u8],
+	) -> anyhow::Result<Option<PacketProtocol<T>>> {
 		self.buf.extend_from_slice(bytes);
 
 		match self.state {
 			PacketBufState::Header => {
-				if (self.buf.len() < PacketBuf::HEADER_LEN) {
-					return None;
+				if self.buf.len() < self.format.len {
+					return Ok(None);
 				}
 
 				self.state = PacketBufState::Content;
 				self.process(&[])
 			}
 			PacketBufState::Content => {
-				let content_length = u32::from_be_bytes(self.buf[5..9].try_into().unwrap());
-				let packet_length = PacketBuf::HEADER_LEN + content_length as usize;
+				if !self.format.is_synced(&self.buf) {
+					self.resync();
+					return self.process(&[]);
+				}
+
+				let header = self.format.decode(&self.buf).and_then(|header| {
+					check_content_length(header.2)?;
+					Ok(header)
+				});
+				let (id, protocol, content_length) = match header {
+					Ok(header) => header,
+					Err(error) => {
+						// The header cannot be trusted, skip it so the next call starts over.
+						self.buf.drain(..self.format.len);
+						self.state = PacketBufState::Header;
+						return Err(error);
+					}
+				};
+				let packet_length = self.format.len + content_length as usize;
 				if self.buf.len() < packet_length {
-					return None;
+					return Ok(None);
 				}
 
-				let packet_bytes: Vec<u8> = self.buf.drain(0..packet_length).collect();
-				Some(PacketProtocol::try_from(packet_bytes.as_slice()).unwrap())
+				let content: Vec<u8> = self
+					.buf
+					.drain(0..packet_length)
+					.skip(self.format.len)
+					.collect();
+				self.state = PacketBufState::Header;
+				Ok(Some(PacketProtocol::Raw {
+					id,
+					protocol,
+					content,
+				}))
+			}
+		}
+	}
+}
+
+impl PacketBuf {
+	// Drops the bytes before the next sync word after the start of the buffer, returning
+	// whether one was found. Without one, only a tail that may hold the start of a sync word
+	// is kept. Formats without a sync word cannot resync.
+	pub fn resync(&mut self) -> bool {
+		let Some(sync_offset) = self.format.sync_offset else {
+			return false;
+		};
+
+		self.state = PacketBufState::Header;
+		let found = self
+			.buf
+			.windows(SYNC_WORD.len())
+			.enumerate()
+			.skip(sync_offset + 1)
+			.find(|(_, window)| *window == SYNC_WORD)
+			.map(|(position, _)| position - sync_offset);
+
+		match found {
+			Some(start) => {
+				self.buf.drain(..start);
+				true
+			}
+			None => {
+				let keep = sync_offset + SYNC_WORD.len() - 1;
+				self.buf.drain(..self.buf.len().saturating_sub(keep));
+				false
 			}
 		}
 	}
 }
 
+impl Default for PacketBuf {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
 impl<T: Packet> PacketProtocol<T> {
+	pub fn protocol_id(&self) -> u8 {
+		match self {
+			PacketProtocol::Raw { protocol, .. } => *protocol,
+			PacketProtocol::Zero(_) => ZERO_PROTOCOL_ID,
+			PacketProtocol::Json(_) => JSON_PROTOCOL_ID,
+			PacketProtocol::ZeroVarint(_) => ZERO_VARINT_PROTOCOL_ID,
+		}
+	}
+
+	pub fn packet_id(&self) -> u32 {
+		match self {
+			PacketProtocol::Raw { id, .. } => *id,
+			_ => T::id(),
+		}
+	}
+
+	// Size of the serialized packet, header included. Exact for the binary protocols, JSON
+	// gives no hint.
+	pub fn serialized_size_hint(&self) -> Option<usize> {
+		let content = match self {
+			PacketProtocol::Raw { content, .. } => Some(content.len()),
+			PacketProtocol::Zero(data) => ZeroCodec.size_hint(data),
+			PacketProtocol::Json(data) => JsonCodec.size_hint(data),
+			PacketProtocol::ZeroVarint(data) => ZeroVarintCodec.size_hint(data),
+		};
+
+		content.map(|content| HEADER_LEN + content)
+	}
+
 	pub fn serialize(self) -> anyhow::Result<Vec<u8>> {
-		let (id, protocol, content) = match self {
+		match self {
 			PacketProtocol::Raw {
 				id,
 				protocol,
 				content,
-			} => (id, protocol, content),
-			PacketProtocol::Zero(data) => {
-				use bincode::Options;
-
-				let serialized_data = bincode::options()
-					.with_big_endian()
-					.with_fixint_encoding()
-					.serialize(&data)?;
-
-				(T::id(), ZERO_PROTOCOL_ID, serialized_data)
+			} => {
+				let mut packet = Vec::with_capacity(HEADER_LEN + content.len());
+				packet.extend(encode_header(id, protocol, content.len() as u32));
+				packet.extend(content);
+				Ok(packet)
 			}
-			PacketProtocol::Json(data) => {
-				let serialized_data = serde_json::to_string(&data).map(|s| s.into_bytes())?;
+			PacketProtocol::Zero(data) => Self::serialize_with(&ZeroCodec, &data),
+			PacketProtocol::Json(data) => Self::serialize_with(&JsonCodec, &data),
+			PacketProtocol::ZeroVarint(data) => Self::serialize_with(&ZeroVarintCodec, &data),
+		}
+	}
 
-				(T::id(), JSON_PROTOCOL_ID, serialized_data)
-			}
-		};
+	// Serializes with any codec, built in or not, under the codec's protocol id.
+	pub fn serialize_with<C: WireCodec>(codec: &C, data: &T) -> anyhow::Result<Vec<u8>> {
+		let mut packet = Vec::with_capacity(HEADER_LEN + codec.size_hint(data).unwrap_or(0));
+		packet.resize(HEADER_LEN, 0);
+		codec.encode_into(data, &mut packet)?;
 
-		Ok([
-			id.to_be_bytes().as_slice(),
-			protocol.to_be_bytes().as_slice(),
-			(content.len() as u32).to_be_bytes().as_slice(),
-			content.as_slice(),
-		]
-		.concat())
+		let content_length = (packet.len() - HEADER_LEN) as u32;
+		HeaderFormat::DEFAULT.write(&mut packet, T::id(), codec.protocol_id(), content_length);
+		Ok(packet)
 	}
 
 	pub fn deserialize(self) -> anyhow::Result<T> {
@@ -129,45 +425,292 @@ impl<T: Packet> PacketProtocol<T> {
 					anyhow::bail!("Id mismatch");
 				}
 
-				match protocol {
-					ZERO_PROTOCOL_ID => {
-						use bincode::Options;
-						Ok(bincode::options()
-							.with_big_endian()
-							.with_fixint_encoding()
-							.deserialize::<T>(&content)?)
-					}
-					JSON_PROTOCOL_ID => Ok(serde_json::from_slice(&content)?),
-					_ => anyhow::bail!("Unknown protocol"),
-				}
+				decode_content(protocol, &content)
 			}
 			PacketProtocol::Zero(data) => Ok(data),
 			PacketProtocol::Json(data) => Ok(data),
+			PacketProtocol::ZeroVarint(data) => Ok(data),
 		}
 	}
+
+	// Like `deserialize`, but raw content is decoded with `codec`, which has to be the one the
+	// packet was sent with.
+	pub fn deserialize_with<C: WireCodec>(self, codec: &C) -> anyhow::Result<T> {
+		match self {
+			PacketProtocol::Raw {
+				id,
+				protocol,
+				content,
+			} => {
+				if id != T::id() {
+					anyhow::bail!("Id mismatch");
+				}
+				if protocol != codec.protocol_id() {
+					anyhow::bail!("Protocol mismatch");
+				}
+
+				codec.decode(&content)
+			}
+			packet => packet.deserialize(),
+		}
+	}
+
+	// Indented JSON of the packet's data for logs, whatever protocol it goes out with. The wire
+	// keeps using compact JSON.
+	pub fn to_pretty_json(&self) -> anyhow::Result<String> {
+		let json = match self {
+			PacketProtocol::Raw {
+				id,
+				protocol,
+				content,
+			} => {
+				if *id != T::id() {
+					anyhow::bail!("Id mismatch");
+				}
+
+				serde_json::to_string_pretty(&decode_content::<T>(*protocol, content)?)?
+			}
+			PacketProtocol::Zero(data) => serde_json::to_string_pretty(data)?,
+			PacketProtocol::Json(data) => serde_json::to_string_pretty(data)?,
+			PacketProtocol::ZeroVarint(data) => serde_json::to_string_pretty(data)?,
+		};
+
+		Ok(json)
+	}
+
+	// Encodes a typed packet into the `Raw` variant it would arrive as, raw ones stay as is.
+	pub fn into_raw(self) -> anyhow::Result<PacketProtocol<T>> {
+		let protocol = self.protocol_id();
+		let content = match self {
+			PacketProtocol::Raw { .. } => return Ok(self),
+			PacketProtocol::Zero(data) => ZeroCodec.encode(&data)?,
+			PacketProtocol::Json(data) => JsonCodec.encode(&data)?,
+			PacketProtocol::ZeroVarint(data) => ZeroVarintCodec.encode(&data)?,
+		};
+
+		Ok(PacketProtocol::Raw {
+			id: T::id(),
+			protocol,
+			content,
+		})
+	}
+
+	// Transforms the packet, keeping the protocol it is sent under. A raw packet is decoded
+	// first, failing like `deserialize` would.
+	pub fn map<U: Packet, F: FnOnce(T) -> U>(self, f: F) -> anyhow::Result<PacketProtocol<U>> {
+		let protocol = self.protocol_id();
+		let data = f(self.deserialize()?);
+
+		Ok(match protocol {
+			JSON_PROTOCOL_ID => PacketProtocol::Json(data),
+			ZERO_VARINT_PROTOCOL_ID => PacketProtocol::ZeroVarint(data),
+			_ => PacketProtocol::Zero(data),
+		})
+	}
+}
+
+fn decode_content<T: DeserializeOwned>(protocol: u8, content: &[u8]) -> anyhow::Result<T> {
+	match protocol {
+		ZERO_PROTOCOL_ID => ZeroCodec.decode(content),
+		JSON_PROTOCOL_ID => JsonCodec.decode(content),
+		ZERO_VARINT_PROTOCOL_ID => ZeroVarintCodec.decode(content),
+		_ => anyhow::bail!("Unknown protocol"),
+	}
+}
+
+impl WireCodec for ZeroCodec {
+	fn protocol_id(&self) -> u8 {
+		ZERO_PROTOCOL_ID
+	}
+
+	fn encode_into<T: Serialize>(&self, value: &T, out: &mut Vec<u8>) -> anyhow::Result<()> {
+		use bincode::Options;
+
+		Ok(bincode::options()
+			.with_big_endian()
+			.with_fixint_encoding()
+			.serialize_into(out, value)?)
+	}
+
+	fn size_hint<T: Serialize>(&self, value: &T) -> Option<usize> {
+		use bincode::Options;
+
+		bincode::options()
+			.with_big_endian()
+			.with_fixint_encoding()
+			.serialized_size(value)
+			.ok()
+			.map(|size| size as usize)
+	}
+
+	fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> anyhow::Result<T> {
+		use bincode::Options;
+
+		Ok(bincode::options()
+			.with_big_endian()
+			.with_fixint_encoding()
+			.deserialize(bytes)?)
+	}
+}
+
+impl WireCodec for ZeroVarintCodec {
+	fn protocol_id(&self) -> u8 {
+		ZERO_VARINT_PROTOCOL_ID
+	}
+
+	fn encode_into<T: Serialize>(&self, value: &T, out: &mut Vec<u8>) -> anyhow::Result<()> {
+		use bincode::Options;
+
+		Ok(bincode::options()
+			.with_big_endian()
+			.with_varint_encoding()
+			.serialize_into(out, value)?)
+	}
+
+	fn size_hint<T: Serialize>(&self, value: &T) -> Option<usize> {
+		use bincode::Options;
+
+		bincode::options()
+			.with_big_endian()
+			.with_varint_encoding()
+			.serialized_size(value)
+			.ok()
+			.map(|size| size as usize)
+	}
+
+	fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> anyhow::Result<T> {
+		use bincode::Options;
+
+		Ok(bincode::options()
+			.with_big_endian()
+			.with_varint_encoding()
+			.deserialize(bytes)?)
+	}
+}
+
+impl WireCodec for JsonCodec {
+	fn protocol_id(&self) -> u8 {
+		JSON_PROTOCOL_ID
+	}
+
+	fn encode_into<T: Serialize>(&self, value: &T, out: &mut Vec<u8>) -> anyhow::Result<()> {
+		Ok(serde_json::to_writer(out, value)?)
+	}
+
+	fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> anyhow::Result<T> {
+		Ok(serde_json::from_slice(bytes)?)
+	}
+}
+
+impl ServerPacket {
+	// Like `==`, but the float fields only need to be within `epsilon` of each other.
+	pub fn approx_eq(&self, other: &ServerPacket, epsilon: f32) -> bool {
+		self.player_id == other.player_id
+			&& self.position.approx_eq(other.position, epsilon)
+			&& self.velocity.approx_eq(other.velocity, epsilon)
+			&& (self.orientation - other.orientation).abs() <= epsilon
+			&& self.design == other.design
+			&& self.propulsor == other.propulsor
+			&& self.hits == other.hits
+			&& self.invulnerable == other.invulnerable
+	}
 }
 
 impl Packet for ClientPacket {
 	fn id() -> u32 {
 		0x00
 	}
+
+	fn priority() -> u8 {
+		PRIORITY_HIGH
+	}
 }
 
+// Sent every tick, a late one is superseded by the next anyway.
 impl Packet for ServerPacket {
 	fn id() -> u32 {
 		0x01
 	}
+
+	fn priority() -> u8 {
+		PRIORITY_LOW
+	}
+}
+
+impl Packet for WorldSnapshot {
+	fn id() -> u32 {
+		0x02
+	}
+
+	fn priority() -> u8 {
+		PRIORITY_LOW
+	}
+}
+
+impl Packet for WelcomePacket {
+	fn id() -> u32 {
+		0x03
+	}
+
+	fn priority() -> u8 {
+		PRIORITY_HIGH
+	}
+}
+
+impl Packet for StatsPacket {
+	fn id() -> u32 {
+		0x04
+	}
+
+	fn priority() -> u8 {
+		PRIORITY_LOW
+	}
+}
+
+impl Packet for PingPacket {
+	fn id() -> u32 {
+		0x05
+	}
+
+	fn priority() -> u8 {
+		PRIORITY_HIGH
+	}
+}
+
+impl Packet for PongPacket {
+	fn id() -> u32 {
+		0x06
+	}
+
+	fn priority() -> u8 {
+		PRIORITY_HIGH
+	}
+}
+
+impl Packet for AckPacket {
+	fn id() -> u32 {
+		0x07
+	}
+}
+
+impl Packet for ControlPacket {
+	fn id() -> u32 {
+		0x08
+	}
+
+	fn priority() -> u8 {
+		PRIORITY_HIGH
+	}
 }
 
 impl<T: Packet> TryFrom<&[u8]> for PacketProtocol<T> {
 	type Error = anyhow::Error;
 
 	fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
-		let id = u32::from_be_bytes(bytes[0..4].try_into()?);
-		let protocol = u8::from_be_bytes(bytes[4..5].try_into()?);
-
-		let expected_length = u32::from_be_bytes(bytes[5..9].try_into()?) as usize;
-		let content = bytes[9..].to_vec();
+		let (id, protocol, expected_length) = decode_header(bytes)?;
+		check_content_length(expected_length)?;
+		let expected_length = expected_length as usize;
+		let content = bytes[HEADER_LEN..].to_vec();
 
 		if expected_length != content.len() {
 			anyhow::bail!("Length mismatch");
@@ -180,3 +723,925 @@ impl<T: Packet> TryFrom<&[u8]> for PacketProtocol<T> {
 		})
 	}
 }
+
+impl<T: Packet> TryFrom<Vec<u8>> for PacketProtocol<T> {
+	type Error = anyhow::Error;
+
+	// Reuses the allocation of `bytes` for the content instead of copying it.
+	fn try_from(mut bytes: Vec<u8>) -> Result<Self, Self::Error> {
+		let (id, protocol, expected_length) = decode_header(&bytes)?;
+		check_content_length(expected_length)?;
+		bytes.drain(0..HEADER_LEN);
+
+		if expected_length as usize != bytes.len() {
+			anyhow::bail!("Length mismatch");
+		}
+
+		Ok(Self::Raw {
+			id,
+			protocol,
+			content: bytes,
+		})
+	}
+}
+
+// Reads exactly one packet, blocking until its header and whole content have arrived.
+pub fn read_packet<T: Packet, R: Read>(reader: &mut R) -> anyhow::Result<PacketProtocol<T>> {
+	let mut header = [0; HEADER_LEN];
+	reader.read_exact(&mut header)?;
+
+	let (id, protocol, content_length) = decode_header(&header)?;
+	check_content_length(content_length)?;
+
+	let mut content = vec![0; content_length as usize];
+	reader.read_exact(&mut content)?;
+
+	Ok(PacketProtocol::Raw {
+		id,
+		protocol,
+		content,
+	})
+}
+
+// Writes the whole packet, header included, blocking until the writer took all of it.
+pub fn write_packet<T: Packet, W: Write>(
+	writer: &mut W,
+	packet: PacketProtocol<T>,
+) -> anyhow::Result<()> {
+	writer.write_all(&packet.serialize()?)?;
+	Ok(())
+}
+
+// Round-trips a default `T` through every protocol, failing on any difference.
+pub fn self_test<T: Packet + Default + PartialEq + fmt::Debug>() -> anyhow::Result<()> {
+	let packets = [
+		PacketProtocol::Zero(T::default()),
+		PacketProtocol::Json(T::default()),
+		PacketProtocol::ZeroVarint(T::default()),
+	];
+
+	for packet in packets {
+		let protocol = packet.protocol_id();
+		let bytes = packet.serialize()?;
+		let decoded: T = PacketProtocol::try_from(bytes.as_slice())?.deserialize()?;
+		if decoded != T::default() {
+			anyhow::bail!("Packet {} changed through protocol {protocol}", T::id());
+		}
+	}
+
+	Ok(())
+}
+
+// Checks every built-in packet: unique ids, and each survives every protocol.
+pub fn self_test_all() -> anyhow::Result<()> {
+	let mut ids = [
+		ClientPacket::id(),
+		ServerPacket::id(),
+		WorldSnapshot::id(),
+		WelcomePacket::id(),
+		StatsPacket::id(),
+		PingPacket::id(),
+		PongPacket::id(),
+		AckPacket::id(),
+		ControlPacket::id(),
+	];
+	ids.sort_unstable();
+	if let Some(pair) = ids.windows(2).find(|pair| pair[0] == pair[1]) {
+		anyhow::bail!("Packet id {} used twice", pair[0]);
+	}
+
+	self_test::<ClientPacket>()?;
+	self_test::<ServerPacket>()?;
+	self_test::<WorldSnapshot>()?;
+	self_test::<WelcomePacket>()?;
+	self_test::<StatsPacket>()?;
+	self_test::<PingPacket>()?;
+	self_test::<PongPacket>()?;
+	self_test::<AckPacket>()?;
+	self_test::<ControlPacket>()
+}
+
+// Byte offsets of every header field, the single description of the header layout.
+// The id and content length are 32 bits big-endian, the protocol id is a single byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeaderFormat {
+	// Where `SYNC_WORD` goes, if the format has one.
+	pub sync_offset: Option<usize>,
+	pub id_offset: usize,
+	pub protocol_offset: usize,
+	pub content_length_offset: usize,
+	pub len: usize,
+}
+
+impl HeaderFormat {
+	// [  16 bits  |  32 bits  |   8 bits    |     32 bits     ]
+	// [ sync word | packet id | protocol id | size of content ]
+	pub const DEFAULT: HeaderFormat = HeaderFormat {
+		sync_offset: Some(0),
+		id_offset: 2,
+		protocol_offset: 6,
+		content_length_offset: 7,
+		len: 11,
+	};
+
+	// Writes the fields into the first `len` bytes of `header`, leaving any other bytes untouched.
+	pub fn write(&self, header: &mut [u8], id: u32, protocol: u8, content_length: u32) {
+		if let Some(offset) = self.sync_offset {
+			header[offset..offset + SYNC_WORD.len()].copy_from_slice(&SYNC_WORD);
+		}
+		header[self.id_offset..self.id_offset + 4].copy_from_slice(&id.to_be_bytes());
+		header[self.protocol_offset] = protocol;
+		header[self.content_length_offset..self.content_length_offset + 4]
+			.copy_from_slice(&content_length.to_be_bytes());
+	}
+
+	pub fn encode(&self, id: u32, protocol: u8, content_length: u32) -> Vec<u8> {
+		let mut header = vec![0; self.len];
+		self.write(&mut header, id, protocol, content_length);
+
+		header
+	}
+
+	pub fn decode(&self, bytes: &[u8]) -> anyhow::Result<(u32, u8, u32)> {
+		if bytes.len() < self.len {
+			anyhow::bail!("Header too short");
+		}
+		if !self.is_synced(bytes) {
+			anyhow::bail!("Header does not start with the sync word");
+		}
+
+		Ok((
+			self.id(bytes)?,
+			self.protocol(bytes)?,
+			self.content_length(bytes)?,
+		))
+	}
+
+	// Whether the sync word is where it belongs, always true for formats without one.
+	pub fn is_synced(&self, bytes: &[u8]) -> bool {
+		match self.sync_offset {
+			Some(offset) => bytes.get(offset..offset + SYNC_WORD.len()) == Some(&SYNC_WORD[..]),
+			None => true,
+		}
+	}
+
+	pub fn id(&self, bytes: &[u8]) -> anyhow::Result<u32> {
+		read_u32(bytes, self.id_offset)
+	}
+
+	pub fn protocol(&self, bytes: &[u8]) -> anyhow::Result<u8> {
+		match bytes.get(self.protocol_offset) {
+			Some(protocol) => Ok(*protocol),
+			None => anyhow::bail!("Header too short"),
+		}
+	}
+
+	pub fn content_length(&self, bytes: &[u8]) -> anyhow::Result<u32> {
+		read_u32(bytes, self.content_length_offset)
+	}
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> anyhow::Result<u32> {
+	match bytes.get(offset..offset + 4) {
+		Some(field) => Ok(u32::from_be_bytes(field.try_into()?)),
+		None => anyhow::bail!("Header too short"),
+	}
+}
+
+pub fn encode_header(id: u32, protocol: u8, content_length: u32) -> [u8; HEADER_LEN] {
+	let mut header = [0; HEADER_LEN];
+	HeaderFormat::DEFAULT.write(&mut header, id, protocol, content_length);
+
+	header
+}
+
+pub fn decode_header(bytes: &[u8]) -> anyhow::Result<(u32, u8, u32)> {
+	HeaderFormat::DEFAULT.decode(bytes)
+}
+
+pub fn peek_id(bytes: &[u8]) -> anyhow::Result<u32> {
+	HeaderFormat::DEFAULT.id(bytes)
+}
+
+pub fn peek_protocol(bytes: &[u8]) -> anyhow::Result<u8> {
+	HeaderFormat::DEFAULT.protocol(bytes)
+}
+
+// Whether a header's protocol id names one of the content encodings above.
+pub fn is_known_protocol(protocol: u8) -> bool {
+	matches!(
+		protocol,
+		ZERO_PROTOCOL_ID | JSON_PROTOCOL_ID | ZERO_VARINT_PROTOCOL_ID
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn sample_snapshot() -> WorldSnapshot {
+		let ship = |id: u32, x: f32| ServerPacket {
+			player_id: id,
+			position: Vector { x, y: -x },
+			velocity: Vector { x: 0.5, y: 0.25 },
+			orientation: 5.0,
+			design: 2,
+			propulsor: 0b0101,
+			hits: id,
+			invulnerable: id == 0,
+		};
+
+		WorldSnapshot {
+			ships: vec![ship(0, 1.5), ship(1, -3.0)],
+			bullets: vec![BulletState {
+				id: 9,
+				position: Vector { x: 2.0, y: 4.0 },
+				velocity: Vector { x: -1.0, y: 0.0 },
+				owner_id: 1,
+			}],
+			tick: 42,
+		}
+	}
+
+	#[test]
+	fn world_snapshot_round_trips() {
+		let snapshot = sample_snapshot();
+		for packet in [
+			PacketProtocol::Zero(snapshot.clone()),
+			PacketProtocol::Json(snapshot.clone()),
+		] {
+			let bytes = packet.serialize().unwrap();
+			assert_eq!(decode_header(&bytes).unwrap().0, WorldSnapshot::id());
+
+			let decoded: WorldSnapshot = PacketProtocol::try_from(bytes.as_slice())
+				.unwrap()
+				.deserialize()
+				.unwrap();
+			assert_eq!(
+				serde_json::to_value(decoded).unwrap(),
+				serde_json::to_value(&snapshot).unwrap()
+			);
+		}
+	}
+
+	#[test]
+	fn peeks_header_fields() {
+		let input = ClientPacket {
+			player_id: 3,
+			orientation: 0.0,
+			propulsor: 0,
+			client_tick: 0,
+			sequence: 0,
+		};
+		let bytes = PacketProtocol::Json(input).serialize().unwrap();
+		assert_eq!(peek_id(&bytes).unwrap(), ClientPacket::id());
+		assert_eq!(peek_protocol(&bytes).unwrap(), 1);
+
+		// Each field is bounds checked on its own.
+		assert!(peek_id(&bytes[..5]).is_err());
+		assert_eq!(peek_id(&bytes[..6]).unwrap(), ClientPacket::id());
+		assert!(peek_protocol(&bytes[..6]).is_err());
+		assert!(peek_id(&[]).is_err());
+	}
+
+	#[test]
+	fn varint_round_trips_smaller() {
+		let packet = ClientPacket {
+			player_id: 1,
+			orientation: 5.0,
+			propulsor: 3,
+			client_tick: 0,
+			sequence: 0,
+		};
+		let fixint = PacketProtocol::Zero(packet.clone()).serialize().unwrap();
+		let varint = PacketProtocol::ZeroVarint(packet.clone())
+			.serialize()
+			.unwrap();
+		assert_eq!(peek_protocol(&varint).unwrap(), 2);
+		assert!(varint.len() < fixint.len());
+
+		let decoded: ClientPacket = PacketProtocol::try_from(varint.as_slice())
+			.unwrap()
+			.deserialize()
+			.unwrap();
+		assert_eq!(
+			serde_json::to_value(decoded).unwrap(),
+			serde_json::to_value(&packet).unwrap()
+		);
+	}
+
+	#[test]
+	fn owning_conversion_reuses_the_buffer() {
+		let bytes = PacketProtocol::Zero(ClientPacket {
+			player_id: 2,
+			orientation: 0.0,
+			propulsor: 0,
+			client_tick: 0,
+			sequence: 0,
+		})
+		.serialize()
+		.unwrap();
+		let borrowed = PacketProtocol::<ClientPacket>::try_from(bytes.as_slice()).unwrap();
+
+		let allocation = bytes.as_ptr();
+		let owned = PacketProtocol::<ClientPacket>::try_from(bytes).unwrap();
+		let (
+			PacketProtocol::Raw {
+				id,
+				protocol,
+				content,
+			},
+			PacketProtocol::Raw {
+				id: borrowed_id,
+				protocol: borrowed_protocol,
+				content: borrowed_content,
+			},
+		) = (owned, borrowed)
+		else {
+			panic!("expected raw packets");
+		};
+		assert_eq!(
+			(id, protocol, &content),
+			(borrowed_id, borrowed_protocol, &borrowed_content)
+		);
+		// The content was moved down in place, not copied into a new allocation.
+		assert_eq!(content.as_ptr(), allocation);
+
+		assert!(PacketProtocol::<ClientPacket>::try_from(vec![0; 3]).is_err());
+	}
+
+	#[test]
+	fn reports_protocol_and_packet_ids() {
+		let packet = ClientPacket {
+			player_id: 1,
+			orientation: 0.0,
+			propulsor: 0,
+			client_tick: 0,
+			sequence: 0,
+		};
+		let raw = PacketProtocol::<ClientPacket>::Raw {
+			id: 99,
+			protocol: 7,
+			content: Vec::new(),
+		};
+		assert_eq!((raw.protocol_id(), raw.packet_id()), (7, 99));
+
+		for (packet, protocol) in [
+			(PacketProtocol::Zero(packet.clone()), 0),
+			(PacketProtocol::Json(packet.clone()), 1),
+			(PacketProtocol::ZeroVarint(packet), 2),
+		] {
+			assert_eq!(packet.protocol_id(), protocol);
+			assert_eq!(packet.packet_id(), ClientPacket::id());
+
+			// Same as what ends up in the header.
+			let bytes = packet.serialize().unwrap();
+			assert_eq!(peek_protocol(&bytes).unwrap(), protocol);
+		}
+	}
+
+	#[test]
+	fn header_format_reemits_identical_bytes() {
+		let packet = ClientPacket {
+			player_id: 1,
+			orientation: 0.0,
+			propulsor: 0,
+			client_tick: 0,
+			sequence: 0,
+		};
+		let bytes = PacketProtocol::Json(packet).serialize().unwrap();
+		let header = &bytes[..HEADER_LEN];
+		let (id, protocol, length) = HeaderFormat::DEFAULT.decode(header).unwrap();
+		assert_eq!(HeaderFormat::DEFAULT.encode(id, protocol, length), header);
+		assert_eq!(encode_header(id, protocol, length), header);
+
+		// An extended layout with a version byte in front and no sync word.
+		let extended = HeaderFormat {
+			sync_offset: None,
+			id_offset: 1,
+			protocol_offset: 5,
+			content_length_offset: 6,
+			len: 10,
+		};
+		let mut header = extended.encode(0xdead_beef, 3, 17);
+		header[0] = 1;
+		assert_eq!(extended.decode(&header).unwrap(), (0xdead_beef, 3, 17));
+		let mut reemitted = header.clone();
+		extended.write(&mut reemitted, 0xdead_beef, 3, 17);
+		assert_eq!(reemitted, header);
+	}
+
+	#[test]
+	fn fractional_orientation_round_trips() {
+		let packet = ClientPacket {
+			player_id: 0,
+			orientation: -2.345_67,
+			propulsor: 0,
+			client_tick: 0,
+			sequence: 0,
+		};
+		for protocol in [
+			PacketProtocol::Zero(packet.clone()),
+			PacketProtocol::Json(packet.clone()),
+			PacketProtocol::ZeroVarint(packet.clone()),
+		] {
+			let bytes = protocol.serialize().unwrap();
+			let decoded: ClientPacket = PacketProtocol::try_from(bytes.as_slice())
+				.unwrap()
+				.deserialize()
+				.unwrap();
+			assert_eq!(decoded.orientation, packet.orientation);
+		}
+	}
+
+	// JSON with its bytes reversed, under a protocol id of its own.
+	struct ReversedJsonCodec;
+
+	impl WireCodec for ReversedJsonCodec {
+		fn protocol_id(&self) -> u8 {
+			0x10
+		}
+
+		fn encode_into<T: Serialize>(&self, value: &T, out: &mut Vec<u8>) -> anyhow::Result<()> {
+			let mut json = serde_json::to_vec(value)?;
+			json.reverse();
+			out.extend(json);
+			Ok(())
+		}
+
+		fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> anyhow::Result<T> {
+			let mut json = bytes.to_vec();
+			json.reverse();
+			Ok(serde_json::from_slice(&json)?)
+		}
+	}
+
+	#[test]
+	fn custom_codec_round_trips() {
+		let packet = ClientPacket {
+			player_id: 5,
+			orientation: 0.5,
+			propulsor: 0,
+			client_tick: 0,
+			sequence: 0,
+		};
+		let bytes = PacketProtocol::serialize_with(&ReversedJsonCodec, &packet).unwrap();
+		assert_eq!(peek_id(&bytes).unwrap(), ClientPacket::id());
+		assert_eq!(peek_protocol(&bytes).unwrap(), 0x10);
+		assert_eq!(bytes.last(), Some(&b'{'));
+
+		let raw = PacketProtocol::<ClientPacket>::try_from(bytes.as_slice()).unwrap();
+		let decoded = raw.clone().deserialize_with(&ReversedJsonCodec).unwrap();
+		assert_eq!((decoded.player_id, decoded.orientation), (5, 0.5));
+		// The built in codecs do not know the protocol, and a mismatched codec is refused.
+		assert!(raw.clone().deserialize().is_err());
+		assert!(raw.deserialize_with(&JsonCodec).is_err());
+	}
+
+	#[test]
+	fn built_in_codecs_match_the_variants() {
+		let packet = ClientPacket {
+			player_id: 0,
+			orientation: 0.0,
+			propulsor: 0,
+			client_tick: 0,
+			sequence: 3,
+		};
+		assert_eq!(
+			PacketProtocol::serialize_with(&ZeroCodec, &packet).unwrap(),
+			PacketProtocol::Zero(packet.clone()).serialize().unwrap()
+		);
+		let bytes = PacketProtocol::Json(packet).serialize().unwrap();
+		let raw = PacketProtocol::<ClientPacket>::try_from(bytes.as_slice()).unwrap();
+		assert_eq!(raw.deserialize_with(&JsonCodec).unwrap().sequence, 3);
+	}
+
+	#[test]
+	fn rejects_oversized_declared_length() {
+		let length = MAX_CONTENT_LENGTH + 1;
+		let header = encode_header(ClientPacket::id(), 0, length);
+		let expected = ProtocolError::TooLarge {
+			length,
+			max: MAX_CONTENT_LENGTH,
+		};
+
+		let error = PacketProtocol::<ClientPacket>::try_from(&header[..]).unwrap_err();
+		assert_eq!(error.downcast_ref::<ProtocolError>(), Some(&expected));
+		let error = PacketProtocol::<ClientPacket>::try_from(header.to_vec()).unwrap_err();
+		assert_eq!(error.downcast_ref::<ProtocolError>(), Some(&expected));
+		assert!(check_content_length(MAX_CONTENT_LENGTH).is_ok());
+	}
+
+	#[test]
+	fn reads_packets_from_a_stream() {
+		let packet = |player_id, client_tick| ClientPacket {
+			player_id,
+			orientation: 0.5,
+			propulsor: 0,
+			client_tick,
+			sequence: 0,
+		};
+		let mut bytes = PacketProtocol::Zero(packet(1, 0)).serialize().unwrap();
+		bytes.extend(PacketProtocol::Json(packet(2, 7)).serialize().unwrap());
+		let mut reader = std::io::Cursor::new(bytes);
+
+		let first = read_packet::<ClientPacket, _>(&mut reader).unwrap();
+		assert_eq!(first.protocol_id(), 0);
+		assert_eq!(first.deserialize().unwrap().player_id, 1);
+		let second = read_packet::<ClientPacket, _>(&mut reader).unwrap();
+		assert_eq!(second.protocol_id(), 1);
+		let second = second.deserialize().unwrap();
+		assert_eq!((second.player_id, second.client_tick), (2, 7));
+		assert!(read_packet::<ClientPacket, _>(&mut reader).is_err());
+
+		// Content cut short.
+		let bytes = PacketProtocol::Zero(packet(1, 0)).serialize().unwrap();
+		let mut reader = std::io::Cursor::new(&bytes[..bytes.len() - 1]);
+		assert!(read_packet::<ClientPacket, _>(&mut reader).is_err());
+	}
+
+	#[test]
+	fn written_packets_read_back() {
+		let packet = ServerPacket {
+			player_id: 4,
+			position: Vector { x: 1.5, y: -2.0 },
+			velocity: Vector { x: 0.0, y: 0.0 },
+			orientation: 0.0,
+			design: 0,
+			propulsor: 0,
+			hits: 3,
+			invulnerable: false,
+		};
+		let mut bytes = Vec::new();
+		write_packet(&mut bytes, PacketProtocol::Zero(packet.clone())).unwrap();
+		write_packet(&mut bytes, PacketProtocol::ZeroVarint(packet.clone())).unwrap();
+
+		let mut reader = bytes.as_slice();
+		for protocol in [0, 2] {
+			let read = read_packet::<ServerPacket, _>(&mut reader).unwrap();
+			assert_eq!(read.protocol_id(), protocol);
+			let read = read.deserialize().unwrap();
+			assert_eq!(
+				(read.player_id, read.position, read.hits),
+				(4, packet.position, 3)
+			);
+		}
+		assert!(reader.is_empty());
+	}
+
+	#[test]
+	fn size_hint_is_exact_for_binary_protocols() {
+		let packet = ServerPacket {
+			player_id: 300,
+			position: Vector { x: 1.5, y: -2.0 },
+			velocity: Vector { x: 0.0, y: 0.0 },
+			orientation: 0.0,
+			design: 0,
+			propulsor: 0,
+			hits: 70_000,
+			invulnerable: true,
+		};
+		let packets = [
+			PacketProtocol::Zero(packet.clone()),
+			PacketProtocol::ZeroVarint(packet.clone()),
+		];
+		for packet in packets {
+			let hint = packet.serialized_size_hint().unwrap();
+			let bytes = packet.serialize().unwrap();
+			assert_eq!(hint, bytes.len());
+			// The raw form of the same bytes gives the same hint.
+			let raw = PacketProtocol::<ServerPacket>::try_from(bytes.as_slice()).unwrap();
+			assert_eq!(raw.serialized_size_hint(), Some(hint));
+		}
+
+		assert_eq!(PacketProtocol::Json(packet).serialized_size_hint(), None);
+	}
+
+	#[test]
+	fn packet_buf_skips_oversized_headers() {
+		let valid = PacketProtocol::Zero(ClientPacket {
+			player_id: 7,
+			orientation: 0.0,
+			propulsor: 0,
+			client_tick: 0,
+			sequence: 0,
+		})
+		.serialize()
+		.unwrap();
+		let mut bytes = encode_header(ClientPacket::id(), 0, MAX_CONTENT_LENGTH + 1).to_vec();
+		bytes.extend(&valid);
+
+		let mut buf = PacketBuf::new();
+		let error = buf.process::<ClientPacket>(&bytes).unwrap_err();
+		assert!(error.downcast_ref::<ProtocolError>().is_some());
+		// The bad header is gone, the packet after it comes out next.
+		let packet = buf.process::<ClientPacket>(&[]).unwrap().unwrap();
+		assert_eq!(packet.deserialize().unwrap().player_id, 7);
+		assert!(buf.process::<ClientPacket>(&[]).unwrap().is_none());
+	}
+
+	// Runs every parser that sees untrusted bytes, only errors are allowed to come out.
+	fn parse_untrusted<T: Packet>(bytes: &[u8]) {
+		if let Ok(packet) = PacketProtocol::<T>::try_from(bytes) {
+			let _ = packet.deserialize();
+		}
+		if let Ok(packet) = PacketProtocol::<T>::try_from(bytes.to_vec()) {
+			let _ = packet.deserialize_with(&ZeroVarintCodec);
+		}
+		let _ = read_packet::<T, _>(&mut &bytes[..]);
+
+		let mut buf = PacketBuf::new();
+		for chunk in bytes.chunks(5) {
+			let mut packet = buf.process::<T>(chunk);
+			while let Ok(Some(raw)) = packet {
+				let _ = raw.deserialize();
+				packet = buf.process::<T>(&[]);
+			}
+		}
+	}
+
+	fn parse_untrusted_everywhere(bytes: &[u8]) {
+		parse_untrusted::<ClientPacket>(bytes);
+		parse_untrusted::<ServerPacket>(bytes);
+		parse_untrusted::<WorldSnapshot>(bytes);
+		parse_untrusted::<WelcomePacket>(bytes);
+		let _ = StateMessage::decode(bytes);
+		let _ = peek_id(bytes);
+	}
+
+	#[test]
+	fn random_bytes_never_panic() {
+		// xorshift32, deterministic so a failure can be replayed.
+		let mut state = 164u32;
+		let mut next = move || {
+			state ^= state << 13;
+			state ^= state >> 17;
+			state ^= state << 5;
+			state
+		};
+		for _ in 0..2_000 {
+			let mut bytes = Vec::new();
+			// Half of the inputs get a well formed header, so the content decoders run too.
+			if next().is_multiple_of(2) {
+				let length = match next() % 3 {
+					0 => next(),
+					_ => next() % 48,
+				};
+				let protocol = (next() % 4) as u8;
+				bytes.extend(encode_header(next() % 10, protocol, length));
+			}
+			let extra = next() % 64;
+			bytes.extend((0..extra).map(|_| next() as u8));
+
+			parse_untrusted_everywhere(&bytes);
+		}
+	}
+
+	#[test]
+	fn truncated_packets_never_panic() {
+		let packets = [
+			PacketProtocol::Zero(sample_snapshot()).serialize().unwrap(),
+			PacketProtocol::Json(sample_snapshot()).serialize().unwrap(),
+			PacketProtocol::ZeroVarint(sample_snapshot())
+				.serialize()
+				.unwrap(),
+		];
+		for packet in packets {
+			for end in 0..packet.len() {
+				parse_untrusted_everywhere(&packet[..end]);
+				parse_untrusted_everywhere(&packet[end..]);
+			}
+		}
+	}
+
+	#[derive(Debug, PartialEq, Serialize, Deserialize)]
+	struct Heading {
+		player_id: u32,
+		degrees: f32,
+	}
+
+	impl Packet for Heading {
+		fn id() -> u32 {
+			0x20
+		}
+	}
+
+	#[test]
+	fn map_converts_the_packet_and_keeps_the_protocol() {
+		let to_heading = |packet: ClientPacket| Heading {
+			player_id: packet.player_id,
+			degrees: packet.orientation.to_degrees(),
+		};
+		let packet = ClientPacket {
+			player_id: 5,
+			orientation: std::f32::consts::FRAC_PI_2,
+			propulsor: 0,
+			client_tick: 0,
+			sequence: 0,
+		};
+		let expected = Heading {
+			player_id: 5,
+			degrees: 90.0,
+		};
+
+		let mapped = PacketProtocol::Json(packet.clone())
+			.map(to_heading)
+			.unwrap();
+		assert_eq!(mapped.protocol_id(), JSON_PROTOCOL_ID);
+		assert_eq!(mapped.packet_id(), Heading::id());
+		assert_eq!(mapped.deserialize().unwrap(), expected);
+
+		// Raw packets are decoded first.
+		let bytes = PacketProtocol::ZeroVarint(packet).serialize().unwrap();
+		let raw = PacketProtocol::<ClientPacket>::try_from(bytes.as_slice()).unwrap();
+		let mapped = raw.map(to_heading).unwrap();
+		assert_eq!(mapped.protocol_id(), ZERO_VARINT_PROTOCOL_ID);
+		assert_eq!(mapped.deserialize().unwrap(), expected);
+
+		let bytes = PacketProtocol::Zero(sample_snapshot()).serialize().unwrap();
+		let raw = PacketProtocol::<ClientPacket>::try_from(bytes).unwrap();
+		assert!(raw.map(to_heading).is_err());
+	}
+
+	#[test]
+	fn approx_eq_finds_the_ship_that_moved() {
+		let before = sample_snapshot();
+		let mut after = before.clone();
+		after.ships[1].position.x += 0.5;
+		// Float noise from the round trip through the simulation.
+		after.ships[0].velocity.y += 1e-6;
+
+		let changed: Vec<u32> = before
+			.ships
+			.iter()
+			.zip(&after.ships)
+			.filter(|(before, after)| !before.approx_eq(after, 1e-4))
+			.map(|(_, after)| after.player_id)
+			.collect();
+		assert_eq!(changed, vec![1]);
+		// Exact comparison sees the noise as well.
+		assert_ne!(before.ships[0], after.ships[0]);
+
+		let mut hit = before.ships[0].clone();
+		hit.hits += 1;
+		assert!(!before.ships[0].approx_eq(&hit, 1.0));
+	}
+
+	#[test]
+	fn packet_buf_reassembles_other_header_formats() {
+		// Version byte in front of the packet id and two spare bytes at the end, no sync word.
+		let format = HeaderFormat {
+			sync_offset: None,
+			id_offset: 1,
+			protocol_offset: 5,
+			content_length_offset: 6,
+			len: 12,
+		};
+		let frame = |player_id: u32| {
+			let content = ZeroCodec
+				.encode(&ClientPacket {
+					player_id,
+					orientation: 0.0,
+					propulsor: 0,
+					client_tick: 0,
+					sequence: 0,
+				})
+				.unwrap();
+			let mut bytes = vec![0; format.len];
+			format.write(&mut bytes, ClientPacket::id(), 0, content.len() as u32);
+			bytes[0] = 2;
+			bytes.extend(content);
+			bytes
+		};
+		let mut bytes = frame(1);
+		bytes.extend(frame(2));
+
+		let mut buf = PacketBuf::with_format(format);
+		let mut players = Vec::new();
+		for byte in bytes {
+			if let Some(packet) = buf.process::<ClientPacket>(&[byte]).unwrap() {
+				players.push(packet.deserialize().unwrap().player_id);
+			}
+		}
+		assert_eq!(players, vec![1, 2]);
+	}
+
+	#[test]
+	fn built_in_packets_pass_the_self_test() {
+		self_test::<ClientPacket>().unwrap();
+		self_test::<ServerPacket>().unwrap();
+		self_test_all().unwrap();
+	}
+
+	// Loses `version` on the wire, it comes back as 0 instead of the default 1.
+	#[derive(Debug, PartialEq, Serialize, Deserialize)]
+	struct Lossy {
+		#[serde(skip)]
+		version: u8,
+	}
+
+	impl Default for Lossy {
+		fn default() -> Self {
+			Lossy { version: 1 }
+		}
+	}
+
+	impl Packet for Lossy {
+		fn id() -> u32 {
+			0x21
+		}
+	}
+
+	#[test]
+	fn self_test_catches_lossy_packets() {
+		let error = self_test::<Lossy>().unwrap_err();
+		assert!(error.to_string().contains("Packet 33 changed"));
+	}
+
+	#[test]
+	fn into_raw_matches_serialize() {
+		let packet = ClientPacket {
+			player_id: 3,
+			orientation: 0.25,
+			propulsor: 0b1001,
+			client_tick: 8,
+			sequence: 8,
+		};
+		let typed = [
+			PacketProtocol::Zero(packet.clone()),
+			PacketProtocol::Json(packet.clone()),
+			PacketProtocol::ZeroVarint(packet.clone()),
+		];
+		for typed in typed {
+			let bytes = typed.clone().serialize().unwrap();
+			let raw = typed.into_raw().unwrap();
+			let PacketProtocol::Raw { id, content, .. } = &raw else {
+				panic!("expected a raw packet");
+			};
+			assert_eq!(*id, ClientPacket::id());
+			assert_eq!(content[..], bytes[HEADER_LEN..]);
+			// Forwarding the raw packet gives the same bytes, and raw stays raw.
+			let raw = raw.into_raw().unwrap();
+			assert_eq!(raw.serialize().unwrap(), bytes);
+		}
+	}
+
+	#[test]
+	fn packet_buf_resyncs_after_junk() {
+		let packet = |player_id: u32| {
+			PacketProtocol::Zero(ClientPacket {
+				player_id,
+				..Default::default()
+			})
+			.serialize()
+			.unwrap()
+		};
+		let mut header = packet(0)[..HEADER_LEN].to_vec();
+		assert!(HeaderFormat::DEFAULT.decode(&header).is_ok());
+		header[0] = 0;
+		assert!(HeaderFormat::DEFAULT.decode(&header).is_err());
+
+		// Junk ending in half a sync word, then two packets with junk between them.
+		let mut bytes = vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, SYNC_WORD[0]];
+		bytes.extend(packet(1));
+		bytes.extend([SYNC_WORD[0], 0]);
+		bytes.extend(packet(2));
+
+		let mut buf = PacketBuf::new();
+		let mut players = Vec::new();
+		for chunk in bytes.chunks(5) {
+			let mut packet = buf.process::<ClientPacket>(chunk);
+			while let Some(raw) = packet.unwrap() {
+				players.push(raw.deserialize().unwrap().player_id);
+				packet = buf.process(&[]);
+			}
+		}
+		assert_eq!(players, vec![1, 2]);
+
+		// Without a sync word in sight only a possible start of one is kept.
+		let mut buf = PacketBuf::new();
+		buf.buf.extend([0; 20]);
+		buf.buf.push(SYNC_WORD[0]);
+		assert!(!buf.resync());
+		assert_eq!(buf.buf, vec![SYNC_WORD[0]]);
+	}
+
+	#[test]
+	fn pretty_json_is_indented_and_parses_back() {
+		let snapshot = sample_snapshot();
+		let pretty = PacketProtocol::Zero(snapshot.clone())
+			.to_pretty_json()
+			.unwrap();
+		assert!(pretty.contains('\n'));
+		assert_eq!(
+			serde_json::from_str::<WorldSnapshot>(&pretty).unwrap(),
+			snapshot
+		);
+
+		// Raw packets are decoded first, the wire form stays compact.
+		let wire = PacketProtocol::Json(snapshot).serialize().unwrap();
+		assert!(!wire.contains(&b'\n'));
+		let raw = PacketProtocol::<WorldSnapshot>::try_from(wire.as_slice()).unwrap();
+		assert_eq!(raw.to_pretty_json().unwrap(), pretty);
+	}
+}
@@ -0,0 +1,195 @@
+use std::io::{Read, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::{ClientData, Game, ShipId};
+use crate::protocol::{ClientPacket, PacketProtocol};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayEntry {
+	pub tick: u64,
+	pub player_index: usize,
+	pub bytes: Vec<u8>,
+}
+
+// Keeps every inbound packet so a session can be reproduced later, see `Game::set_recorder`.
+#[derive(Debug, Default)]
+pub struct ReplayRecorder {
+	entries: Vec<ReplayEntry>,
+}
+
+impl ReplayRecorder {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn record(&mut self, tick: u64, player_index: usize, bytes: &[u8]) {
+		self.entries.push(ReplayEntry {
+			tick,
+			player_index,
+			bytes: bytes.to_vec(),
+		});
+	}
+
+	pub fn entries(&self) -> &[ReplayEntry] {
+		&self.entries
+	}
+
+	pub fn save<W: Write>(&self, writer: W) -> anyhow::Result<()> {
+		Ok(bincode::serialize_into(writer, &self.entries)?)
+	}
+
+	pub fn into_player(self, dt: f32) -> ReplayPlayer {
+		ReplayPlayer {
+			entries: self.entries,
+			dt,
+		}
+	}
+}
+
+// Feeds recorded packets back into a headless game, one ship per recorded player.
+pub struct ReplayPlayer {
+	entries: Vec<ReplayEntry>,
+	dt: f32,
+}
+
+impl ReplayPlayer {
+	pub fn load<R: Read>(reader: R, dt: f32) -> anyhow::Result<Self> {
+		Ok(Self {
+			entries: bincode::deserialize_from(reader)?,
+			dt,
+		})
+	}
+
+	// Replays the session up to and including `last_tick`. `new_game` builds the game the
+	// session started from: same world settings and ships spawned before anyone joined.
+	pub fn play(&self, new_game: impl FnOnce() -> Game, last_tick: u64) -> anyhow::Result<Game> {
+		let mut game = new_game();
+
+		let players = self
+			.entries
+			.iter()
+			.map(|entry| entry.player_index + 1)
+			.max()
+			.unwrap_or(0);
+		let ship_ids: Vec<ShipId> = (0..players).map(|_| game.spawn_ship()).collect();
+
+		let mut entries = self.entries.iter().peekable();
+		for tick in 0..=last_tick {
+			while let Some(entry) = entries.next_if(|entry| entry.tick == tick) {
+				let packet: ClientPacket =
+					PacketProtocol::try_from(entry.bytes.as_slice())?.deserialize()?;
+
+				game.queue_input(ClientData {
+					ship_id: ship_ids[entry.player_index],
+					..ClientData::from(&packet)
+				});
+			}
+
+			game.apply_all_inputs();
+			game.step(self.dt);
+		}
+
+		Ok(game)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Write;
+
+	use super::*;
+	use crate::game::tests::tcp_pair;
+	use crate::game::BoundsMode;
+
+	const DT: f32 = 1.0 / 60.0;
+
+	fn session_game() -> Game {
+		let mut game = Game::new();
+		game.set_bounds_mode(BoundsMode::Wrap);
+		game.spawn_ship();
+		game
+	}
+
+	fn input(player_id: u32, client_tick: u32) -> Vec<u8> {
+		let packet = ClientPacket {
+			player_id,
+			orientation: client_tick as f32 * 0.3,
+			propulsor: (client_tick % 16) as u8,
+			client_tick,
+			sequence: 0,
+		};
+		PacketProtocol::Zero(packet).serialize().unwrap()
+	}
+
+	#[test]
+	fn records_what_players_send() {
+		let mut game = session_game();
+		game.set_recorder(Some(ReplayRecorder::new()));
+		let mut clients = Vec::new();
+		for _ in 0..2 {
+			let (stream, client) = tcp_pair();
+			game.new_player(stream).unwrap();
+			clients.push(client);
+		}
+
+		for tick in 0..3 {
+			for (index, client) in clients.iter_mut().enumerate() {
+				client.write_all(&input(index as u32 + 1, tick)).unwrap();
+			}
+			game.net_poll();
+			game.apply_all_inputs();
+			game.step(DT);
+		}
+
+		let recorder = game.take_recorder().unwrap();
+		assert!(game.take_recorder().is_none());
+		let expected: Vec<ReplayEntry> = (0..3)
+			.flat_map(|tick| {
+				(0..2).map(move |index| ReplayEntry {
+					tick: tick as u64,
+					player_index: index,
+					bytes: input(index as u32 + 1, tick),
+				})
+			})
+			.collect();
+		assert_eq!(recorder.entries(), expected.as_slice());
+	}
+
+	#[test]
+	fn replays_into_the_session_game() {
+		let mut recorder = ReplayRecorder::new();
+		let last_tick = 30;
+		for tick in (0..=last_tick).step_by(3) {
+			recorder.record(tick, 0, &input(0, tick as u32));
+			recorder.record(tick + 1, 1, &input(0, tick as u32));
+		}
+		let mut saved = Vec::new();
+		recorder.save(&mut saved).unwrap();
+
+		// The same inputs queued by hand on the ships the players got.
+		let mut expected = session_game();
+		let ids = [expected.spawn_ship(), expected.spawn_ship()];
+		for tick in 0..=last_tick {
+			for entry in recorder.entries().iter().filter(|entry| entry.tick == tick) {
+				let packet: ClientPacket = PacketProtocol::try_from(entry.bytes.as_slice())
+					.unwrap()
+					.deserialize()
+					.unwrap();
+				expected.queue_input(ClientData {
+					ship_id: ids[entry.player_index],
+					..ClientData::from(&packet)
+				});
+			}
+			expected.apply_all_inputs();
+			expected.step(DT);
+		}
+
+		let replayed = ReplayPlayer::load(saved.as_slice(), DT)
+			.unwrap()
+			.play(session_game, last_tick)
+			.unwrap();
+		assert_eq!(replayed.ships().len(), 3);
+		assert_eq!(replayed.ships(), expected.ships());
+	}
+}
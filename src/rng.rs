@@ -0,0 +1,45 @@
+// PCG32 (XSH RR), small and fast with good statistical quality. Everything random in the
+// game goes through it so a seed reproduces the same run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rng {
+	state: u64,
+	increment: u64,
+}
+
+impl Rng {
+	const MULTIPLIER: u64 = 6364136223846793005;
+	const STREAM: u64 = 0xda3e39cb94b95bdb;
+
+	pub fn new(seed: u64) -> Self {
+		let mut rng = Rng {
+			state: 0,
+			increment: (Self::STREAM << 1) | 1,
+		};
+		rng.next_u32();
+		rng.state = rng.state.wrapping_add(seed);
+		rng.next_u32();
+
+		rng
+	}
+
+	pub fn next_u32(&mut self) -> u32 {
+		let old = self.state;
+		self.state = old
+			.wrapping_mul(Self::MULTIPLIER)
+			.wrapping_add(self.increment);
+
+		let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+		let rotation = (old >> 59) as u32;
+		xorshifted.rotate_right(rotation)
+	}
+
+	// Uniform in [0, 1), from the top 24 bits so every value is exactly representable.
+	pub fn next_f32(&mut self) -> f32 {
+		(self.next_u32() >> 8) as f32 / (1 << 24) as f32
+	}
+
+	// Uniform in [min, max).
+	pub fn range(&mut self, min: f32, max: f32) -> f32 {
+		min + (max - min) * self.next_f32()
+	}
+}
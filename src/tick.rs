@@ -0,0 +1,317 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub trait Clock {
+	// Time elapsed since an arbitrary, fixed origin.
+	fn now(&self) -> Duration;
+	fn sleep(&mut self, duration: Duration);
+}
+
+pub struct SystemClock {
+	origin: Instant,
+}
+
+impl SystemClock {
+	pub fn new() -> Self {
+		Self {
+			origin: Instant::now(),
+		}
+	}
+}
+
+impl Default for SystemClock {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Clock for SystemClock {
+	fn now(&self) -> Duration {
+		self.origin.elapsed()
+	}
+
+	fn sleep(&mut self, duration: Duration) {
+		thread::sleep(duration);
+	}
+}
+
+// Calls a tick function at a target rate, sleeping only for the time the tick did not use.
+pub struct TickLoop<C: Clock> {
+	clock: C,
+	period: Duration,
+	overruns: u64,
+	// How long to sleep between checks while there is nothing to simulate.
+	idle_period: Duration,
+	paused: bool,
+}
+
+impl<C: Clock> TickLoop<C> {
+	pub fn new(clock: C, tick_rate: f32) -> anyhow::Result<Self> {
+		if !(tick_rate.is_finite() && tick_rate > 0.0) {
+			anyhow::bail!("Invalid tick rate {tick_rate}");
+		}
+
+		Ok(Self {
+			clock,
+			period: Duration::from_secs_f32(1.0 / tick_rate),
+			overruns: 0,
+			idle_period: Self::DEFAULT_IDLE_PERIOD,
+			paused: false,
+		})
+	}
+
+	pub const DEFAULT_IDLE_PERIOD: Duration = Duration::from_millis(250);
+
+	pub fn set_idle_period(&mut self, idle_period: Duration) {
+		self.idle_period = idle_period;
+	}
+
+	pub fn is_paused(&self) -> bool {
+		self.paused
+	}
+
+	pub fn clock(&self) -> &C {
+		&self.clock
+	}
+
+	// Ticks that took longer than the period, i.e. the loop could not keep up.
+	pub fn overruns(&self) -> u64 {
+		self.overruns
+	}
+
+	// Runs `tick` with the seconds elapsed since the previous tick, until it returns false.
+	pub fn run(&mut self, tick: impl FnMut(f32) -> bool) {
+		self.run_unless_idle(|| false, tick);
+	}
+
+	// Like `run`, but while `is_idle` holds the loop only wakes up every idle period to check
+	// again, without ticking. The first tick after a pause gets a single period as its dt
+	// rather than the whole time spent paused.
+	pub fn run_unless_idle(
+		&mut self,
+		mut is_idle: impl FnMut() -> bool,
+		mut tick: impl FnMut(f32) -> bool,
+	) {
+		let mut last = self.clock.now();
+		let mut deadline = last;
+
+		loop {
+			if is_idle() {
+				self.paused = true;
+				self.clock.sleep(self.idle_period);
+				continue;
+			}
+
+			let now = self.clock.now();
+			if self.paused {
+				self.paused = false;
+				last = now.saturating_sub(self.period);
+				deadline = now;
+			}
+
+			let dt = (now - last).as_secs_f32();
+			last = now;
+
+			if !tick(dt) {
+				break;
+			}
+
+			deadline += self.period;
+			let now = self.clock.now();
+			if now < deadline {
+				self.clock.sleep(deadline - now);
+			} else {
+				// Behind schedule, start over from now instead of rushing the next ticks.
+				self.overruns += 1;
+				deadline = now;
+			}
+		}
+	}
+}
+
+// Turns variable frame times into whole simulation steps of a fixed dt, carrying the
+// remainder over to the next frame.
+pub struct FixedStep {
+	dt: f32,
+	accumulator: f32,
+	max_steps: usize,
+	dropped: f32,
+}
+
+impl FixedStep {
+	// More catch-up steps than this per frame would only make a stall worse.
+	pub const DEFAULT_MAX_STEPS: usize = 5;
+
+	pub fn new(dt: f32) -> Self {
+		Self {
+			dt,
+			accumulator: 0.0,
+			max_steps: Self::DEFAULT_MAX_STEPS,
+			dropped: 0.0,
+		}
+	}
+
+	pub fn set_max_steps(&mut self, max_steps: usize) {
+		self.max_steps = max_steps;
+	}
+
+	pub fn dt(&self) -> f32 {
+		self.dt
+	}
+
+	// Simulated seconds thrown away because a frame needed more than `max_steps` steps.
+	pub fn dropped(&self) -> f32 {
+		self.dropped
+	}
+
+	// Runs as many steps as `real_dt` covers, up to `max_steps`, and returns how many ran.
+	pub fn advance(&mut self, real_dt: f32, mut step: impl FnMut(f32)) -> usize {
+		self.accumulator += real_dt;
+
+		let mut steps = 0;
+		while self.accumulator >= self.dt && steps < self.max_steps {
+			step(self.dt);
+			self.accumulator -= self.dt;
+			steps += 1;
+		}
+
+		// Still behind after the last allowed step, drop the backlog instead of carrying it.
+		if self.accumulator >= self.dt {
+			self.dropped += self.accumulator;
+			self.accumulator = 0.0;
+		}
+
+		steps
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::cell::Cell;
+	use std::rc::Rc;
+
+	use super::*;
+
+	// Time only moves when something sleeps or `advance` is called.
+	#[derive(Clone, Default)]
+	struct MockClock {
+		now: Rc<Cell<Duration>>,
+	}
+
+	impl MockClock {
+		fn advance(&self, duration: Duration) {
+			self.now.set(self.now.get() + duration);
+		}
+	}
+
+	impl Clock for MockClock {
+		fn now(&self) -> Duration {
+			self.now.get()
+		}
+
+		fn sleep(&mut self, duration: Duration) {
+			self.advance(duration);
+		}
+	}
+
+	// Ticks run over one simulated second, each doing `work` worth of time.
+	fn ticks_in_a_second(tick_rate: f32, work: Duration) -> (u32, u64) {
+		let clock = MockClock::default();
+		let mut tick_loop = TickLoop::new(clock.clone(), tick_rate).unwrap();
+		let mut ticks = 0;
+		tick_loop.run(|_| {
+			if clock.now() >= Duration::from_secs(1) {
+				return false;
+			}
+			ticks += 1;
+			clock.advance(work);
+			true
+		});
+
+		(ticks, tick_loop.overruns())
+	}
+
+	#[test]
+	fn schedules_the_target_rate() {
+		assert_eq!(ticks_in_a_second(60.0, Duration::from_millis(5)), (60, 0));
+		assert_eq!(ticks_in_a_second(20.0, Duration::ZERO), (20, 0));
+	}
+
+	#[test]
+	fn counts_overruns() {
+		let (ticks, overruns) = ticks_in_a_second(60.0, Duration::from_millis(25));
+		assert_eq!(ticks, 40);
+		assert_eq!(overruns, 40);
+	}
+
+	#[test]
+	fn dt_is_the_period() {
+		let clock = MockClock::default();
+		let mut tick_loop = TickLoop::new(clock.clone(), 50.0).unwrap();
+		let mut dts = Vec::new();
+		tick_loop.run(|dt| {
+			dts.push(dt);
+			dts.len() < 4
+		});
+
+		assert_eq!(dts[0], 0.0);
+		for dt in &dts[1..] {
+			assert!((dt - 0.02).abs() < 1e-6);
+		}
+	}
+
+	#[test]
+	fn rejects_invalid_tick_rates() {
+		for tick_rate in [0.0, -60.0, f32::NAN, f32::INFINITY] {
+			assert!(TickLoop::new(MockClock::default(), tick_rate).is_err());
+		}
+	}
+
+	#[test]
+	fn resumes_after_idle_with_one_period() {
+		let clock = MockClock::default();
+		let mut tick_loop = TickLoop::new(clock.clone(), 50.0).unwrap();
+		let mut idle_checks = 0;
+		let mut dts = Vec::new();
+		tick_loop.run_unless_idle(
+			|| {
+				idle_checks += 1;
+				// Idle for ten checks after the first tick.
+				(2..12).contains(&idle_checks)
+			},
+			|dt| {
+				dts.push(dt);
+				dts.len() < 3
+			},
+		);
+
+		assert!(!tick_loop.is_paused());
+		assert_eq!(dts.len(), 3);
+		// The 2.5s spent paused does not show up as a huge step.
+		assert!((dts[1] - 0.02).abs() < 1e-6);
+		assert!((dts[2] - 0.02).abs() < 1e-6);
+		assert_eq!(clock.now(), Duration::from_millis(2_500 + 40));
+	}
+
+	#[test]
+	fn catch_up_steps_are_capped() {
+		let mut fixed = FixedStep::new(0.125);
+		let mut steps = 0;
+		// A ten second stall would need 80 steps.
+		assert_eq!(
+			fixed.advance(10.0, |_| steps += 1),
+			FixedStep::DEFAULT_MAX_STEPS
+		);
+		assert_eq!(steps, FixedStep::DEFAULT_MAX_STEPS);
+		assert_eq!(fixed.dropped(), 10.0 - 5.0 * 0.125);
+
+		// The backlog is gone, the next frame runs at the normal rate.
+		assert_eq!(fixed.advance(0.25, |_| ()), 2);
+		// A remainder below one step is carried over.
+		assert_eq!(fixed.advance(0.1, |_| ()), 0);
+		assert_eq!(fixed.advance(0.025, |_| ()), 1);
+
+		fixed.set_max_steps(1);
+		assert_eq!(fixed.advance(0.5, |_| ()), 1);
+	}
+}
@@ -1,6 +1,30 @@
 use serde::{Deserialize, Serialize};
 
-pub use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+pub use core::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+// Every transcendental function the vector math needs goes through here, so the rest of the
+// module only depends on `core` and the backend can be swapped for `libm` on `no_std` targets.
+mod math {
+	pub fn sqrt(x: f32) -> f32 {
+		x.sqrt()
+	}
+
+	pub fn atan2(y: f32, x: f32) -> f32 {
+		y.atan2(x)
+	}
+
+	pub fn sin(x: f32) -> f32 {
+		x.sin()
+	}
+
+	pub fn cos(x: f32) -> f32 {
+		x.cos()
+	}
+
+	pub fn sqrt64(x: f64) -> f64 {
+		x.sqrt()
+	}
+}
 
 #[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
 pub struct Vector {
@@ -8,6 +32,179 @@ pub struct Vector {
 	pub y: f32,
 }
 
+impl Vector {
+	pub fn from_angle(radians: f32) -> Vector {
+		Vector {
+			x: math::cos(radians),
+			y: math::sin(radians),
+		}
+	}
+
+	// Uniformly distributed direction. `sample` yields uniform values in [0, 1).
+	pub fn random_unit(mut sample: impl FnMut() -> f32) -> Vector {
+		Vector::from_angle(sample() * core::f32::consts::TAU)
+	}
+
+	// Uniformly distributed point inside the circle of `radius` around the origin, the sqrt
+	// keeps the density even over the area.
+	pub fn random_in_circle(radius: f32, mut sample: impl FnMut() -> f32) -> Vector {
+		let distance = radius * math::sqrt(sample());
+		Vector::random_unit(sample) * distance
+	}
+
+	pub fn from_polar(radius: f32, theta: f32) -> Vector {
+		Vector::from_angle(theta) * radius
+	}
+
+	// Length and angle, the inverse of `from_polar`.
+	pub fn to_polar(&self) -> (f32, f32) {
+		(self.length(), self.angle())
+	}
+
+	pub fn with_x(self, x: f32) -> Vector {
+		Vector { x, ..self }
+	}
+
+	pub fn with_y(self, y: f32) -> Vector {
+		Vector { y, ..self }
+	}
+
+	pub fn dot(&self, rhs: Vector) -> f32 {
+		self.x * rhs.x + self.y * rhs.y
+	}
+
+	// Z component of the 3D cross product, positive when `rhs` is counter-clockwise from self.
+	pub fn perp_dot(&self, rhs: Vector) -> f32 {
+		self.x * rhs.y - self.y * rhs.x
+	}
+
+	// Signed angle to rotate self onto `other`, in [-PI, PI].
+	pub fn angle_between(&self, other: Vector) -> f32 {
+		math::atan2(self.perp_dot(other), self.dot(other))
+	}
+
+	pub fn length(&self) -> f32 {
+		math::sqrt(self.x * self.x + self.y * self.y)
+	}
+
+	// Unit vector in the same direction, `None` when shorter than `epsilon`.
+	pub fn try_normalize(&self, epsilon: f32) -> Option<Vector> {
+		let length = self.length();
+		if length < epsilon || !length.is_finite() {
+			return None;
+		}
+
+		Some(*self / length)
+	}
+
+	pub fn normalize_or_zero(&self) -> Vector {
+		self.try_normalize(f32::EPSILON)
+			.unwrap_or(Vector { x: 0.0, y: 0.0 })
+	}
+
+	pub fn distance_squared(&self, other: Vector) -> f32 {
+		let difference = *self - other;
+		difference.dot(difference)
+	}
+
+	// |dx| + |dy|, the distance along grid axes.
+	pub fn manhattan_distance(&self, other: Vector) -> f32 {
+		let difference = (*self - other).abs();
+		difference.x + difference.y
+	}
+
+	// max(|dx|, |dy|), the number of grid steps when diagonals count as one.
+	pub fn chebyshev_distance(&self, other: Vector) -> f32 {
+		let difference = (*self - other).abs();
+		difference.x.max(difference.y)
+	}
+
+	pub fn angle(&self) -> f32 {
+		math::atan2(self.y, self.x)
+	}
+
+	pub fn rotate(&self, radians: f32) -> Vector {
+		let (sin, cos) = (math::sin(radians), math::cos(radians));
+
+		Vector {
+			x: self.x * cos - self.y * sin,
+			y: self.x * sin + self.y * cos,
+		}
+	}
+
+	pub fn abs(&self) -> Vector {
+		Vector {
+			x: self.x.abs(),
+			y: self.y.abs(),
+		}
+	}
+
+	// Sign of each component, zero components stay zero unlike `f32::signum`.
+	pub fn signum(&self) -> Vector {
+		let sign = |value: f32| if value == 0.0 { 0.0 } else { value.signum() };
+
+		Vector {
+			x: sign(self.x),
+			y: sign(self.y),
+		}
+	}
+
+	// Quarter turns without trigonometry, exact for any vector.
+	pub fn rotate90_cw(&self) -> Vector {
+		Vector {
+			x: self.y,
+			y: -self.x,
+		}
+	}
+
+	pub fn rotate90_ccw(&self) -> Vector {
+		Vector {
+			x: -self.y,
+			y: self.x,
+		}
+	}
+
+	// Whether both components are within `epsilon` of the other's.
+	pub fn approx_eq(&self, other: Vector, epsilon: f32) -> bool {
+		(self.x - other.x).abs() <= epsilon && (self.y - other.y).abs() <= epsilon
+	}
+
+	pub fn is_finite(&self) -> bool {
+		self.x.is_finite() && self.y.is_finite()
+	}
+
+	// Clamps in place, reporting which axes were out of bounds.
+	pub fn clamp_to_bounds(&mut self, min: Vector, max: Vector) -> (bool, bool) {
+		let x_clamped = self.x < min.x || self.x > max.x;
+		let y_clamped = self.y < min.y || self.y > max.y;
+
+		self.x = self.x.clamp(min.x, max.x);
+		self.y = self.y.clamp(min.y, max.y);
+
+		(x_clamped, y_clamped)
+	}
+}
+
+// Batch versions of `+=` and `rotate` for many entities at once. The loops go over plain
+// iterators with no indexing, so the compiler is free to vectorize them.
+pub fn translate_all(points: &mut [Vector], delta: Vector) {
+	for point in points.iter_mut() {
+		point.x += delta.x;
+		point.y += delta.y;
+	}
+}
+
+// Rotates every point around the origin. The sine and cosine are only computed once.
+pub fn rotate_all(points: &mut [Vector], radians: f32) {
+	let (sin, cos) = (math::sin(radians), math::cos(radians));
+
+	for point in points.iter_mut() {
+		let (x, y) = (point.x, point.y);
+		point.x = x * cos - y * sin;
+		point.y = x * sin + y * cos;
+	}
+}
+
 impl From<(f32, f32)> for Vector {
 	fn from(value: (f32, f32)) -> Self {
 		Vector {
@@ -51,8 +248,9 @@ impl Sub<Vector> for Vector {
 impl Mul<Vector> for Vector {
 	type Output = f32;
 
+	// Dot product, prefer `Vector::dot` for readability.
 	fn mul(self, rhs: Vector) -> Self::Output {
-		self.x * rhs.x + self.y * rhs.y
+		self.dot(rhs)
 	}
 }
 
@@ -127,3 +325,493 @@ impl DivAssign<f32> for Vector {
 		self.y /= rhs;
 	}
 }
+
+// Double-precision counterpart used where accumulated error matters, e.g. long-running
+// server-side simulation. `Vector` stays the wire type; converting back quantizes to `f32`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Vector64 {
+	pub x: f64,
+	pub y: f64,
+}
+
+// Only what the physics needs, for everything else convert to `Vector` first.
+impl Vector64 {
+	pub fn dot(&self, rhs: Vector64) -> f64 {
+		self.x * rhs.x + self.y * rhs.y
+	}
+
+	pub fn length(&self) -> f64 {
+		math::sqrt64(self.dot(*self))
+	}
+
+	pub fn distance_squared(&self, other: Vector64) -> f64 {
+		let difference = *self - other;
+		difference.dot(difference)
+	}
+
+	// Same as `Vector::clamp_to_bounds`.
+	pub fn clamp_to_bounds(&mut self, min: Vector64, max: Vector64) -> (bool, bool) {
+		let x_clamped = self.x < min.x || self.x > max.x;
+		let y_clamped = self.y < min.y || self.y > max.y;
+
+		self.x = self.x.clamp(min.x, max.x);
+		self.y = self.y.clamp(min.y, max.y);
+
+		(x_clamped, y_clamped)
+	}
+
+	pub fn with_x(self, x: f64) -> Vector64 {
+		Vector64 { x, ..self }
+	}
+
+	pub fn with_y(self, y: f64) -> Vector64 {
+		Vector64 { y, ..self }
+	}
+}
+
+impl From<Vector> for Vector64 {
+	fn from(value: Vector) -> Self {
+		Vector64 {
+			x: value.x as f64,
+			y: value.y as f64,
+		}
+	}
+}
+
+impl From<Vector64> for Vector {
+	fn from(value: Vector64) -> Self {
+		Vector {
+			x: value.x as f32,
+			y: value.y as f32,
+		}
+	}
+}
+
+impl From<(f64, f64)> for Vector64 {
+	fn from(value: (f64, f64)) -> Self {
+		Vector64 {
+			x: value.0,
+			y: value.1,
+		}
+	}
+}
+
+impl From<[f64; 2]> for Vector64 {
+	fn from(value: [f64; 2]) -> Self {
+		Vector64 {
+			x: value[0],
+			y: value[1],
+		}
+	}
+}
+
+impl Add<Vector64> for Vector64 {
+	type Output = Vector64;
+
+	fn add(self, rhs: Vector64) -> Self::Output {
+		Vector64 {
+			x: self.x + rhs.x,
+			y: self.y + rhs.y,
+		}
+	}
+}
+
+impl Sub<Vector64> for Vector64 {
+	type Output = Vector64;
+
+	fn sub(self, rhs: Vector64) -> Self::Output {
+		Vector64 {
+			x: self.x - rhs.x,
+			y: self.y - rhs.y,
+		}
+	}
+}
+
+impl Mul<Vector64> for Vector64 {
+	type Output = f64;
+
+	fn mul(self, rhs: Vector64) -> Self::Output {
+		self.x * rhs.x + self.y * rhs.y
+	}
+}
+
+impl Mul<f64> for Vector64 {
+	type Output = Vector64;
+
+	fn mul(self, rhs: f64) -> Self::Output {
+		Vector64 {
+			x: self.x * rhs,
+			y: self.y * rhs,
+		}
+	}
+}
+
+impl Mul<Vector64> for f64 {
+	type Output = Vector64;
+
+	fn mul(self, rhs: Vector64) -> Self::Output {
+		Vector64 {
+			x: self * rhs.x,
+			y: self * rhs.y,
+		}
+	}
+}
+
+impl Div<f64> for Vector64 {
+	type Output = Vector64;
+
+	fn div(self, rhs: f64) -> Self::Output {
+		Vector64 {
+			x: self.x / rhs,
+			y: self.y / rhs,
+		}
+	}
+}
+
+impl Neg for Vector64 {
+	type Output = Vector64;
+
+	fn neg(self) -> Self::Output {
+		Vector64 {
+			x: -self.x,
+			y: -self.y,
+		}
+	}
+}
+
+impl AddAssign<Vector64> for Vector64 {
+	fn add_assign(&mut self, rhs: Vector64) {
+		self.x += rhs.x;
+		self.y += rhs.y;
+	}
+}
+
+impl SubAssign<Vector64> for Vector64 {
+	fn sub_assign(&mut self, rhs: Vector64) {
+		self.x -= rhs.x;
+		self.y -= rhs.y;
+	}
+}
+
+impl MulAssign<f64> for Vector64 {
+	fn mul_assign(&mut self, rhs: f64) {
+		self.x *= rhs;
+		self.y *= rhs;
+	}
+}
+
+impl DivAssign<f64> for Vector64 {
+	fn div_assign(&mut self, rhs: f64) {
+		self.x /= rhs;
+		self.y /= rhs;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn math_shim_matches_std() {
+		assert_eq!(math::sqrt(2.25), 1.5);
+		assert_eq!(math::atan2(1.0, -1.0), 3.0 * core::f32::consts::FRAC_PI_4);
+		assert_eq!(math::sin(0.5), 0.5f32.sin());
+		assert_eq!(math::cos(0.5), 0.5f32.cos());
+
+		let vector = Vector::from_angle(core::f32::consts::FRAC_PI_3) * 2.0;
+		assert!((vector.length() - 2.0).abs() < 1e-6);
+		assert!((vector.angle() - core::f32::consts::FRAC_PI_3).abs() < 1e-6);
+	}
+
+	#[test]
+	fn vector64_round_trips_through_wire_form() {
+		let precise = Vector64 {
+			x: 1_234.567_890_123,
+			y: -0.000_123_456_789,
+		};
+		let wire = Vector::from(precise);
+		let back = Vector64::from(wire);
+
+		// Half an f32 ulp of relative error at most.
+		let tolerance = f32::EPSILON as f64 / 2.0;
+		assert!((back.x - precise.x).abs() <= precise.x.abs() * tolerance);
+		assert!((back.y - precise.y).abs() <= precise.y.abs() * tolerance);
+
+		// Values that already fit in f32 survive unchanged.
+		assert_eq!(Vector::from(back), wire);
+	}
+
+	#[test]
+	fn vector64_accumulates_less_drift() {
+		let step = 0.1;
+		let mut narrow = Vector { x: 0.0, y: 0.0 };
+		let mut wide = Vector64::default();
+		for _ in 0..100_000 {
+			narrow += Vector { x: step, y: -step };
+			wide += Vector64::from(Vector { x: step, y: -step });
+		}
+
+		let exact = 100_000.0 * step as f64;
+		assert!((wide.x - exact).abs() < 1e-6);
+		assert!((wide.x - exact).abs() < (narrow.x as f64 - exact).abs());
+	}
+
+	#[test]
+	fn is_finite() {
+		assert!(Vector { x: 1.0, y: -2.0 }.is_finite());
+		assert!(!Vector {
+			x: f32::NAN,
+			y: 0.0
+		}
+		.is_finite());
+		assert!(!Vector {
+			x: 0.0,
+			y: f32::INFINITY
+		}
+		.is_finite());
+		assert!(!Vector {
+			x: f32::NEG_INFINITY,
+			y: f32::NAN
+		}
+		.is_finite());
+	}
+
+	#[test]
+	fn clamp_to_bounds_reports_axes() {
+		let (min, max) = (Vector { x: -10.0, y: -10.0 }, Vector { x: 10.0, y: 10.0 });
+		let mut position = Vector { x: 12.0, y: 3.0 };
+		assert_eq!(position.clamp_to_bounds(min, max), (true, false));
+		assert_eq!(position, Vector { x: 10.0, y: 3.0 });
+
+		assert_eq!(position.clamp_to_bounds(min, max), (false, false));
+	}
+
+	#[test]
+	fn dot_matches_operator() {
+		let a = Vector { x: 1.5, y: -2.0 };
+		let b = Vector { x: 4.0, y: 0.5 };
+		assert_eq!(a.dot(b), a * b);
+		assert_eq!(a.dot(b), 5.0);
+		assert_eq!(a.dot(Vector { x: 2.0, y: 1.5 }), 0.0);
+	}
+
+	#[test]
+	fn with_x_and_with_y() {
+		let vector = Vector { x: 1.0, y: 2.0 };
+		assert_eq!(vector.with_x(5.0), Vector { x: 5.0, y: 2.0 });
+		assert_eq!(vector.with_y(-1.0), Vector { x: 1.0, y: -1.0 });
+
+		let vector = Vector64 { x: 1.0, y: 2.0 };
+		assert_eq!(vector.with_x(5.0), Vector64 { x: 5.0, y: 2.0 });
+		assert_eq!(vector.with_y(-1.0), Vector64 { x: 1.0, y: -1.0 });
+	}
+
+	// Deterministic uniform samples in [0, 1), xorshift32.
+	fn sampler(mut state: u32) -> impl FnMut() -> f32 {
+		move || {
+			state ^= state << 13;
+			state ^= state >> 17;
+			state ^= state << 5;
+			(state >> 8) as f32 / (1 << 24) as f32
+		}
+	}
+
+	#[test]
+	fn random_unit_covers_every_direction() {
+		let mut sample = sampler(1);
+		let mut octants = [0; 8];
+		for _ in 0..8_000 {
+			let direction = Vector::random_unit(&mut sample);
+			assert!((direction.length() - 1.0).abs() < 1e-5);
+
+			let angle = direction.angle().rem_euclid(core::f32::consts::TAU);
+			octants[(angle / core::f32::consts::FRAC_PI_4) as usize % 8] += 1;
+		}
+		// About a thousand each.
+		assert!(
+			octants.iter().all(|&count| (850..1150).contains(&count)),
+			"{octants:?}"
+		);
+	}
+
+	#[test]
+	fn random_in_circle_is_uniform_over_the_area() {
+		let mut sample = sampler(2);
+		let mut inner = 0;
+		for _ in 0..8_000 {
+			let point = Vector::random_in_circle(2.0, &mut sample);
+			assert!(point.length() <= 2.0);
+			if point.length() <= 1.0 {
+				inner += 1;
+			}
+		}
+		// The inner circle is a quarter of the area.
+		assert!((1800..2200).contains(&inner), "{inner}");
+	}
+
+	#[test]
+	fn angle_between() {
+		use core::f32::consts::{FRAC_PI_2, PI};
+
+		let right = Vector { x: 1.0, y: 0.0 };
+		assert!((right.angle_between(Vector { x: 0.0, y: 2.0 }) - FRAC_PI_2).abs() < 1e-6);
+		assert!((right.angle_between(Vector { x: 0.0, y: -2.0 }) + FRAC_PI_2).abs() < 1e-6);
+		assert_eq!(right.angle_between(Vector { x: 3.0, y: 0.0 }), 0.0);
+		assert!((right.angle_between(Vector { x: -1.0, y: 0.0 }).abs() - PI).abs() < 1e-6);
+	}
+
+	#[test]
+	fn quarter_turns_are_exact() {
+		let original = Vector {
+			x: 1.234_567,
+			y: -0.000_1,
+		};
+		let mut cw = original;
+		let mut ccw = original;
+		for _ in 0..4 {
+			cw = cw.rotate90_cw();
+			ccw = ccw.rotate90_ccw();
+		}
+		assert_eq!(cw, original);
+		assert_eq!(ccw, original);
+
+		assert_eq!(original.rotate90_cw().rotate90_ccw(), original);
+		assert_eq!(
+			Vector { x: 1.0, y: 0.0 }.rotate90_ccw(),
+			Vector { x: 0.0, y: 1.0 }
+		);
+	}
+
+	#[test]
+	fn abs_and_signum_per_component() {
+		let vector = Vector { x: -2.5, y: 4.0 };
+		assert_eq!(vector.abs(), Vector { x: 2.5, y: 4.0 });
+		assert_eq!(vector.signum(), Vector { x: -1.0, y: 1.0 });
+
+		// Zero has no sign, negative zero included.
+		let zero = Vector { x: 0.0, y: -0.0 };
+		assert_eq!(zero.signum(), Vector { x: 0.0, y: 0.0 });
+		assert_eq!(zero.abs(), Vector { x: 0.0, y: 0.0 });
+		assert_eq!(
+			Vector { x: 0.0, y: -3.0 }.signum(),
+			Vector { x: 0.0, y: -1.0 }
+		);
+	}
+
+	#[test]
+	fn grid_distances() {
+		let a = Vector { x: 1.0, y: -2.0 };
+		let b = Vector { x: -3.0, y: 1.0 };
+		assert_eq!(a.manhattan_distance(b), 7.0);
+		assert_eq!(a.chebyshev_distance(b), 4.0);
+		assert_eq!(b.manhattan_distance(a), 7.0);
+		assert_eq!(a.manhattan_distance(a), 0.0);
+		assert_eq!(a.chebyshev_distance(a), 0.0);
+	}
+
+	#[test]
+	fn try_normalize_refuses_tiny_vectors() {
+		let unit = Vector { x: 3.0, y: -4.0 }.try_normalize(1e-6).unwrap();
+		assert_eq!(unit, Vector { x: 0.6, y: -0.8 });
+		assert!((unit.length() - 1.0).abs() < 1e-6);
+
+		assert_eq!(Vector { x: 1e-7, y: 0.0 }.try_normalize(1e-6), None);
+		assert_eq!(Vector { x: 0.0, y: 0.0 }.try_normalize(1e-6), None);
+		assert_eq!(
+			Vector {
+				x: f32::INFINITY,
+				y: 0.0
+			}
+			.try_normalize(1e-6),
+			None
+		);
+		assert_eq!(
+			Vector { x: 0.0, y: 0.0 }.normalize_or_zero(),
+			Vector { x: 0.0, y: 0.0 }
+		);
+	}
+
+	#[test]
+	fn polar_coordinates() {
+		use core::f32::consts::{FRAC_PI_2, PI};
+
+		assert_eq!(Vector { x: 2.0, y: 0.0 }.to_polar(), (2.0, 0.0));
+		assert_eq!(Vector { x: 0.0, y: 3.0 }.to_polar(), (3.0, FRAC_PI_2));
+		assert_eq!(Vector { x: -1.0, y: 0.0 }.to_polar(), (1.0, PI));
+		assert_eq!(Vector { x: 0.0, y: -1.0 }.to_polar(), (1.0, -FRAC_PI_2));
+		assert!(Vector::from_polar(2.0, FRAC_PI_2).approx_eq(Vector { x: 0.0, y: 2.0 }, 1e-6));
+
+		for vector in [
+			Vector { x: 1.5, y: -2.5 },
+			Vector { x: -0.25, y: 0.75 },
+			Vector { x: 40.0, y: 30.0 },
+		] {
+			let (radius, theta) = vector.to_polar();
+			assert!(Vector::from_polar(radius, theta).approx_eq(vector, 1e-4));
+		}
+	}
+
+	fn scattered_points(count: usize) -> Vec<Vector> {
+		(0..count)
+			.map(|index| {
+				let index = index as f32;
+				Vector {
+					x: index * 0.75 - 300.0,
+					y: 120.0 - index * 1.25,
+				}
+			})
+			.collect()
+	}
+
+	#[test]
+	fn batch_transforms_match_per_point_ones() {
+		let points = scattered_points(1_000);
+		let delta = Vector { x: 3.5, y: -0.25 };
+
+		let mut translated = points.clone();
+		translate_all(&mut translated, delta);
+		let mut rotated = points.clone();
+		rotate_all(&mut rotated, 0.7);
+
+		for (index, point) in points.iter().enumerate() {
+			assert_eq!(translated[index], *point + delta);
+			assert_eq!(rotated[index], point.rotate(0.7));
+		}
+
+		let mut empty: [Vector; 0] = [];
+		translate_all(&mut empty, delta);
+		rotate_all(&mut empty, 0.7);
+	}
+
+	// Timing only, run with `cargo test --release -- --ignored --nocapture`.
+	#[test]
+	#[ignore]
+	fn bench_batch_transforms() {
+		use std::hint::black_box;
+		use std::time::Instant;
+
+		let mut points = scattered_points(10_000);
+		let iterations = 1_000;
+		let delta = Vector { x: 0.5, y: -0.5 };
+
+		let started = Instant::now();
+		for _ in 0..iterations {
+			translate_all(black_box(&mut points), delta);
+			rotate_all(black_box(&mut points), black_box(0.01));
+		}
+		let batch = started.elapsed() / iterations;
+
+		let started = Instant::now();
+		for _ in 0..iterations {
+			for point in black_box(&mut points).iter_mut() {
+				*point += delta;
+			}
+			for point in black_box(&mut points).iter_mut() {
+				*point = point.rotate(black_box(0.01));
+			}
+		}
+		let scalar = started.elapsed() / iterations;
+
+		println!("10k points: batch {batch:?}, scalar {scalar:?}");
+	}
+}
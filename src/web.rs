@@ -0,0 +1,109 @@
+// Plain functions for browser clients talking to the server over a WebSocket.
+//
+// Nothing in here touches sockets or threads so the module builds for
+// `wasm32-unknown-unknown`, and every signature is simple enough to export through
+// `wasm-bindgen`. Integers and floats are always big-endian on the wire: the header is
+// written by hand with `to_be_bytes`, and the Zero protocol runs bincode with
+// `with_big_endian`, which also covers `f32` fields.
+
+use crate::protocol::{ClientPacket, Packet, PacketProtocol, ServerPacket, StateMessage};
+
+pub use crate::protocol::{decode_header, encode_header, HEADER_LEN};
+
+// Decodes one whole state packet (header and content) as broadcast by the server.
+pub fn decode_state_message(bytes: &[u8]) -> anyhow::Result<StateMessage> {
+	let (id, protocol, length) = decode_header(bytes)?;
+	if id != ServerPacket::id() {
+		anyhow::bail!("Not a state packet: id {id}");
+	}
+	if protocol != 0 {
+		anyhow::bail!("State packets only use protocol 0, got {protocol}");
+	}
+
+	let content = &bytes[HEADER_LEN..];
+	if content.len() != length as usize {
+		anyhow::bail!("Expected {length} content bytes, got {}", content.len());
+	}
+
+	StateMessage::decode(content)
+}
+
+pub fn encode_client_packet(packet: ClientPacket) -> anyhow::Result<Vec<u8>> {
+	PacketProtocol::Json(packet).serialize()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::game::tests::tcp_pair;
+	use crate::game::Game;
+
+	#[test]
+	fn decodes_server_state() {
+		let mut game = Game::new();
+		game.new_player(tcp_pair().0).unwrap();
+		let bytes = game.send_server_packet(ServerPacket::id() as i32, 0, 0);
+
+		let state = decode_state_message(&bytes).unwrap();
+		assert_eq!(state.ships.len(), 1);
+		assert_eq!(state.ships[0].player_id, 0);
+		assert!(state.bullets.is_empty());
+	}
+
+	#[test]
+	fn rejects_other_packets() {
+		let packet = ClientPacket {
+			player_id: 0,
+			orientation: 0.0,
+			propulsor: 0,
+			client_tick: 0,
+			sequence: 0,
+		};
+		let bytes = encode_client_packet(packet).unwrap();
+		assert!(decode_state_message(&bytes).is_err());
+		assert!(decode_state_message(&bytes[..HEADER_LEN - 1]).is_err());
+
+		// Cut in the middle of the state.
+		let mut game = Game::new();
+		game.new_player(tcp_pair().0).unwrap();
+		let bytes = game.send_server_packet(ServerPacket::id() as i32, 0, 0);
+		assert!(decode_state_message(&bytes[..bytes.len() - 1]).is_err());
+	}
+
+	#[test]
+	fn encodes_client_packet_as_json() {
+		let packet = ClientPacket {
+			player_id: 3,
+			orientation: 1.5,
+			propulsor: 0b1000,
+			client_tick: 0,
+			sequence: 0,
+		};
+		let bytes = encode_client_packet(packet).unwrap();
+
+		let (id, protocol, length) = decode_header(&bytes).unwrap();
+		assert_eq!(id, ClientPacket::id());
+		assert_eq!(protocol, 1);
+		assert_eq!(length as usize, bytes.len() - HEADER_LEN);
+
+		let decoded: ClientPacket = PacketProtocol::try_from(bytes.as_slice())
+			.unwrap()
+			.deserialize()
+			.unwrap();
+		assert_eq!(
+			(decoded.player_id, decoded.orientation, decoded.propulsor),
+			(3, 1.5, 0b1000)
+		);
+	}
+
+	#[test]
+	fn header_is_big_endian() {
+		let header = encode_header(0x01020304, 2, 0x0a0b0c0d);
+		assert_eq!(header.len(), HEADER_LEN);
+		assert_eq!(&header[0..2], &[0xBA, 0x11]);
+		assert_eq!(&header[2..6], &[1, 2, 3, 4]);
+		assert_eq!(header[6], 2);
+		assert_eq!(&header[7..11], &[0x0a, 0x0b, 0x0c, 0x0d]);
+		assert_eq!(decode_header(&header).unwrap(), (0x01020304, 2, 0x0a0b0c0d));
+	}
+}